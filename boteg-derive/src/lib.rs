@@ -0,0 +1,214 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, ExprLit, Field, Fields, GenericArgument, Lit,
+    PathArguments, Type,
+};
+
+/// Derives `boteg::FromArgs` for a struct with named fields.
+///
+/// The struct also becomes a handler argument (`boteg::FromUpdate`); when parsing fails the
+/// user gets the error and the usage line of the command.
+///
+/// Supported field attributes:
+/// - `#[args(default)]` — use `Default::default()` when the argument is missing;
+/// - `#[args(default = expr)]` — use `expr` when missing, string literals are parsed with `FromStr`
+///   and an invalid one is reported as a parse error;
+/// - `#[args(rest)]` — last field only, takes all remaining arguments via `boteg::FromRest`.
+///
+/// `Option<T>` fields are optional without any attribute.
+#[proc_macro_derive(FromArgs, attributes(args))]
+pub fn derive_from_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Required,
+    Optional(Type),
+    Default(Option<Expr>),
+    Rest,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "FromArgs can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "FromArgs can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut usage = vec![];
+    let mut inits = vec![];
+    let mut has_rest = false;
+    let mut seen_optional = false;
+
+    for (idx, field) in fields.iter().enumerate() {
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let label = ident.to_string();
+
+        if has_rest {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`#[args(rest)]` must be the last field",
+            ));
+        }
+
+        let init = match field_kind(field)? {
+            Kind::Required => {
+                if seen_optional {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "required arguments can not follow optional ones",
+                    ));
+                }
+                usage.push(format!("<{}>", label));
+                quote! { args.parse_at::<#ty>(#idx)? }
+            }
+            Kind::Optional(inner) => {
+                seen_optional = true;
+                usage.push(format!("[{}]", label));
+                quote! { args.parse_opt::<#inner>(#idx)? }
+            }
+            Kind::Default(None) => {
+                seen_optional = true;
+                usage.push(format!("[{}]", label));
+                quote! { args.parse_opt::<#ty>(#idx)?.unwrap_or_default() }
+            }
+            Kind::Default(Some(Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }))) => {
+                seen_optional = true;
+                usage.push(format!("[{}]", label));
+                quote! {
+                    match args.parse_opt::<#ty>(#idx)? {
+                        ::std::option::Option::Some(value) => value,
+                        ::std::option::Option::None => {
+                            <#ty as ::std::str::FromStr>::from_str(#lit).map_err(|err| {
+                                ::boteg::__private::format_err!(
+                                    "Invalid default for `{}`: {}",
+                                    #label,
+                                    err
+                                )
+                            })?
+                        }
+                    }
+                }
+            }
+            Kind::Default(Some(expr)) => {
+                seen_optional = true;
+                usage.push(format!("[{}]", label));
+                quote! { args.parse_opt::<#ty>(#idx)?.unwrap_or_else(|| #expr) }
+            }
+            Kind::Rest => {
+                has_rest = true;
+                usage.push(format!("[{}...]", label));
+                quote! { <#ty as ::boteg::FromRest>::from_rest(args.rest(#idx))? }
+            }
+        };
+
+        inits.push(quote! { #ident: #init });
+    }
+
+    let count = fields.len();
+    let check_len = if has_rest {
+        quote! {}
+    } else {
+        quote! {
+            if args.len() > #count {
+                ::boteg::__private::bail!("Too many arguments, expected at most {}", #count);
+            }
+        }
+    };
+    let usage = usage.join(" ");
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::boteg::FromArgs for #name #ty_generics #where_clause {
+            fn usage() -> ::std::string::String {
+                ::std::string::String::from(#usage)
+            }
+
+            fn from_args(args: &::boteg::Args) -> ::boteg::Fallible<Self> {
+                #check_len
+                ::std::result::Result::Ok(Self {
+                    #(#inits,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::boteg::FromUpdate for #name #ty_generics #where_clause {
+            fn from_update(ctx: &::boteg::Context) -> ::boteg::Fallible<Self> {
+                ::boteg::__private::args_from_update(ctx)
+            }
+        }
+    })
+}
+
+fn field_kind(field: &Field) -> syn::Result<Kind> {
+    let mut kind = None;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("args"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rest") {
+                kind = Some(Kind::Rest);
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                let expr = if meta.input.peek(syn::Token![=]) {
+                    Some(meta.value()?.parse()?)
+                } else {
+                    None
+                };
+                kind = Some(Kind::Default(expr));
+                Ok(())
+            } else {
+                Err(meta.error("expected `default`, `default = ...` or `rest`"))
+            }
+        })?;
+    }
+
+    Ok(match kind {
+        Some(kind) => kind,
+        None => match option_inner(&field.ty) {
+            Some(inner) => Kind::Optional(inner.clone()),
+            None => Kind::Required,
+        },
+    })
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(generics) = &segment.arguments else {
+        return None;
+    };
+    match generics.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
@@ -1,6 +1,16 @@
-use boteg::Fallible;
-use boteg::{CallbackData, InlineKeyboardButton, InlineKeyboardMarkup, Message, ResponseMessage};
+use boteg::{
+    AccessPolicy, Captures, ChatId, Fallible, FromArgs, JobContext, Payload, Schedule, State,
+    TextPattern,
+};
+use boteg::{
+    Args, CallbackData, InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery, InlineQueryAnswer,
+    InlineQueryResult, InlineQueryResultArticle, InputTextMessageContent, Message, PhotoSize,
+    ResponseMessage,
+};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const STATUS_CHAT: ChatId = ChatId(-1001234567890);
 
 #[tokio::main]
 async fn main() -> Fallible<()> {
@@ -9,132 +19,232 @@ async fn main() -> Fallible<()> {
         "bot684490980:AAFLmComOWytWMops7yw4G-MOaIHY0rzpc8".to_owned(),
         Some(get_sert_path("cert.pem")),
         Some(get_sert_path("key.pem")),
-    )?;
-
-    bot.add_command_static("test", |message: Message| {
-        let text = format!("test -- {}", message.text);
-
-        let reply_markup = InlineKeyboardButton {
-            text: "Exchanges".to_string(),
-            callback_data: CallbackData {
-                command: "exchanges".to_string(),
-                message_id: Some(message.message_id),
-            },
-        };
+    )?
+    .with_state(ButtonState::default());
 
-        let keyboard = InlineKeyboardMarkup {
-            inline_keyboard: [vec![reply_markup]],
-        };
+    bot.add_command_static("test", test);
+    bot.add_callback_static("exchanges", exchanges);
+    bot.add_callback_static("test2", test2_callback);
+    bot.add_command_inline_static("test2", test2);
 
-        Box::pin(async move {
+    bot.add_command_static(
+        "transfer",
+        |chat_id: ChatId, transfer: Transfer| async move {
             Ok(ResponseMessage {
-                chat_id: message.chat.id,
-                text,
+                chat_id,
+                text: format!(
+                    "transfer {} {} to {} ({})",
+                    transfer.amount,
+                    transfer.currency,
+                    transfer.to.as_deref().unwrap_or("myself"),
+                    transfer.comment
+                ),
                 parse_mode: None,
-                reply_markup: Some(keyboard),
+                reply_markup: None,
             })
-        })
-    });
+        },
+    );
 
-    let mut button_state = true;
-
-    bot.add_callback_static("exchanges", |message: Message, message_id: Option<u64>| {
-        let text = "Select exchange".to_owned();
-        dbg!(&message);
-
-        let reply_markup = InlineKeyboardButton {
-            text: "Bybit".to_string(),
-            callback_data: CallbackData {
-                command: "exchanges".to_string(),
-                message_id,
-            },
-        };
-
-        let reply_markup2 = InlineKeyboardButton {
-            text: "Kucoin".to_string(),
-            callback_data: CallbackData {
-                command: "exchanges".to_string(),
-                message_id: None,
-            },
-        };
-
-        let keyboard = InlineKeyboardMarkup {
-            inline_keyboard: [vec![reply_markup, reply_markup2]],
-        };
-
-        Box::pin(async move {
-            Ok(ResponseMessage {
-                chat_id: message.chat.id,
-                text,
-                parse_mode: None,
-                reply_markup: Some(keyboard),
-            })
+    bot.restrict_command("transfer", AccessPolicy::new().chat_admins_only())?;
+    bot.reply_when_denied("Only chat administrators can do that");
+
+    bot.describe_command("test", "Reply with a keyboard")?;
+    bot.describe_command("transfer", "Transfer funds: /transfer 10 USDT @bob")?;
+
+    bot.on_photo(|message: Message, photo: Vec<PhotoSize>| async move {
+        let largest = photo.last().map(|size| (size.width, size.height));
+
+        Ok(ResponseMessage {
+            chat_id: message.chat.id,
+            text: format!("photo -- {:?}", largest),
+            parse_mode: None,
+            reply_markup: None,
         })
     });
 
-    bot.add_callback_static("test2", |message: Message, message_id: Option<u64>| {
-        let text = format!("test2 callback -- {} - {:?}", message.text, message_id);
-
-        let reply_markup = InlineKeyboardButton {
-            text: "More examples".to_string(),
-            callback_data: CallbackData {
-                command: "more".to_string(),
-                message_id,
-            },
-        };
-
-        let reply_markup2 = InlineKeyboardButton {
-            text: "Other".to_string(),
-            callback_data: CallbackData {
-                command: "test2".to_string(),
-                message_id: None,
-            },
-        };
-
-        let keyboard = InlineKeyboardMarkup {
-            inline_keyboard: [vec![reply_markup, reply_markup2]],
-        };
-
-        Box::pin(async move {
+    bot.add_text_route(
+        TextPattern::regex(r"^price (?P<coin>\w+)$")?,
+        |chat_id: ChatId, captures: Captures| async move {
             Ok(ResponseMessage {
-                chat_id: message.chat.id,
-                text,
+                chat_id,
+                text: format!("price of {}", captures.name("coin").unwrap_or_default()),
                 parse_mode: None,
-                reply_markup: Some(keyboard),
+                reply_markup: None,
             })
+        },
+    );
+    bot.add_text_route(TextPattern::ignore_case("hello"), test2);
+
+    bot.on_unknown_command(|chat_id: ChatId, args: Args| async move {
+        Ok(ResponseMessage {
+            chat_id,
+            text: format!("unknown command ({} args), try /test", args.len()),
+            parse_mode: None,
+            reply_markup: None,
         })
     });
 
-    bot.add_command_inline_static("test2", |message: Message| {
-        Box::pin(async move {
-            let text = format!("test2 -- {}", message.text);
+    bot.on_inline_query(inline_query);
+
+    bot.schedule(
+        Schedule::cron("0 0 9 * * *")?,
+        |job: JobContext| async move {
+            let enabled = job
+                .state::<ButtonState>()
+                .is_some_and(|state| state.enabled.load(Ordering::Relaxed));
+            job.api()
+                .send_message(ResponseMessage {
+                    chat_id: STATUS_CHAT,
+                    text: format!("daily status: buttons enabled: {}", enabled),
+                    parse_mode: None,
+                    reply_markup: None,
+                })
+                .await?;
+            Ok(())
+        },
+    );
 
-            let reply_markup = InlineKeyboardButton {
-                text: "More examples".to_string(),
-                callback_data: CallbackData {
-                    command: "test2".to_string(),
-                    message_id: Some(message.message_id),
-                },
-            };
+    bot.enable_current_command();
 
-            let keyboard = InlineKeyboardMarkup {
-                inline_keyboard: [vec![reply_markup]],
-            };
+    bot.run().await?;
 
-            Ok(ResponseMessage {
-                chat_id: message.chat.id,
-                text,
-                parse_mode: None,
-                reply_markup: Some(keyboard),
+    Ok(())
+}
+
+async fn test(message: Message, args: Args) -> Fallible<ResponseMessage> {
+    let text = format!("test -- {} ({} args)", message.text, args.len());
+
+    let reply_markup = InlineKeyboardButton {
+        text: "Exchanges".to_string(),
+        callback_data: CallbackData::with_payload("exchanges", &Some(message.message_id))?,
+    };
+
+    let keyboard = InlineKeyboardMarkup {
+        inline_keyboard: [vec![reply_markup]],
+    };
+
+    Ok(ResponseMessage {
+        chat_id: message.chat.id,
+        text,
+        parse_mode: None,
+        reply_markup: Some(keyboard),
+    })
+}
+
+async fn exchanges(
+    message: Message,
+    Payload(message_id): Payload<Option<u64>>,
+    State(state): State<ButtonState>,
+) -> Fallible<ResponseMessage> {
+    let enabled = !state.enabled.fetch_xor(true, Ordering::Relaxed);
+    let text = format!("Select exchange (buttons enabled: {})", enabled);
+    dbg!(&message);
+
+    let reply_markup = InlineKeyboardButton {
+        text: "Bybit".to_string(),
+        callback_data: CallbackData::with_payload("exchanges", &message_id)?,
+    };
+
+    let reply_markup2 = InlineKeyboardButton {
+        text: "Kucoin".to_string(),
+        callback_data: CallbackData::with_payload("exchanges", &None::<u64>)?,
+    };
+
+    let keyboard = InlineKeyboardMarkup {
+        inline_keyboard: [vec![reply_markup, reply_markup2]],
+    };
+
+    Ok(ResponseMessage {
+        chat_id: message.chat.id,
+        text,
+        parse_mode: None,
+        reply_markup: Some(keyboard),
+    })
+}
+
+async fn test2_callback(
+    message: Message,
+    Payload(message_id): Payload<Option<u64>>,
+) -> Fallible<ResponseMessage> {
+    let text = format!("test2 callback -- {} - {:?}", message.text, message_id);
+
+    let reply_markup = InlineKeyboardButton {
+        text: "More examples".to_string(),
+        callback_data: CallbackData::with_payload("more", &message_id)?,
+    };
+
+    let reply_markup2 = InlineKeyboardButton {
+        text: "Other".to_string(),
+        callback_data: CallbackData::with_payload("test2", &None::<u64>)?,
+    };
+
+    let keyboard = InlineKeyboardMarkup {
+        inline_keyboard: [vec![reply_markup, reply_markup2]],
+    };
+
+    Ok(ResponseMessage {
+        chat_id: message.chat.id,
+        text,
+        parse_mode: None,
+        reply_markup: Some(keyboard),
+    })
+}
+
+async fn test2(message: Message) -> Fallible<ResponseMessage> {
+    let text = format!("test2 -- {}", message.text);
+
+    let reply_markup = InlineKeyboardButton {
+        text: "More examples".to_string(),
+        callback_data: CallbackData::with_payload("test2", &Some(message.message_id))?,
+    };
+
+    let keyboard = InlineKeyboardMarkup {
+        inline_keyboard: [vec![reply_markup]],
+    };
+
+    Ok(ResponseMessage {
+        chat_id: message.chat.id,
+        text,
+        parse_mode: None,
+        reply_markup: Some(keyboard),
+    })
+}
+
+async fn inline_query(query: InlineQuery) -> Fallible<InlineQueryAnswer> {
+    let page: usize = query.offset.parse().unwrap_or(0);
+    let results = (page * 10..page * 10 + 10)
+        .map(|idx| {
+            InlineQueryResult::Article(InlineQueryResultArticle {
+                id: idx.to_string(),
+                title: format!("{} #{}", query.query, idx),
+                input_message_content: InputTextMessageContent {
+                    message_text: format!("{} #{}", query.query, idx),
+                    parse_mode: None,
+                },
+                ..Default::default()
             })
         })
-    });
+        .collect();
 
-    bot.enable_current_command();
+    Ok(InlineQueryAnswer {
+        results,
+        next_offset: (page < 4).then(|| (page + 1).to_string()),
+        ..Default::default()
+    })
+}
 
-    bot.run().await?;
+#[derive(Default)]
+struct ButtonState {
+    enabled: AtomicBool,
+}
 
-    Ok(())
+#[derive(FromArgs)]
+struct Transfer {
+    amount: u64,
+    currency: String,
+    to: Option<String>,
+    #[args(rest)]
+    comment: String,
 }
 
 fn get_sert_path(sert: &str) -> PathBuf {
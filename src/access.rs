@@ -0,0 +1,65 @@
+use crate::{ChatId, UserId};
+use std::collections::HashSet;
+
+/// Who may use the bot or a single command, see `Bot::set_access_policy` and
+/// `Bot::restrict_command`.
+///
+/// Bot admins set with `Bot::set_admins` pass every policy.
+#[derive(Debug, Clone, Default)]
+pub struct AccessPolicy {
+    users: HashSet<UserId>,
+    chats: HashSet<ChatId>,
+    admins_only: bool,
+    chat_admins_only: bool,
+}
+
+impl AccessPolicy {
+    /// Policy that allows everyone until restricted further.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the listed users in any chat.
+    ///
+    /// Combined with [`allow_chats`](Self::allow_chats), either the user or the chat must be
+    /// listed.
+    pub fn allow_users<I: IntoIterator<Item = UserId>>(mut self, users: I) -> Self {
+        self.users.extend(users);
+        self
+    }
+
+    /// Allows anyone in the listed chats.
+    pub fn allow_chats<I: IntoIterator<Item = ChatId>>(mut self, chats: I) -> Self {
+        self.chats.extend(chats);
+        self
+    }
+
+    /// Allows bot admins only.
+    pub fn admins_only(mut self) -> Self {
+        self.admins_only = true;
+        self
+    }
+
+    /// Additionally requires the user to be an administrator of the chat, checked with
+    /// `getChatMember` and cached in the bot store.
+    pub fn chat_admins_only(mut self) -> Self {
+        self.chat_admins_only = true;
+        self
+    }
+
+    /// Checks everything except chat administrators, which needs an API call.
+    pub(crate) fn allows(&self, user_id: Option<UserId>, chat_id: Option<ChatId>) -> bool {
+        if self.admins_only {
+            return false;
+        }
+        if self.users.is_empty() && self.chats.is_empty() {
+            return true;
+        }
+        user_id.is_some_and(|user_id| self.users.contains(&user_id))
+            || chat_id.is_some_and(|chat_id| self.chats.contains(&chat_id))
+    }
+
+    pub(crate) fn requires_chat_admin(&self) -> bool {
+        self.chat_admins_only
+    }
+}
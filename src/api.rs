@@ -0,0 +1,434 @@
+use crate::messages::CallbackKey;
+use crate::throttle::{RateLimit, RateLimiter, RetryPolicy};
+use crate::{
+    BotCommand, BotCommandScope, ChatFullInfo, ChatId, ChatMember, Fallible, File,
+    InlineKeyboardMarkup, InlineQueryAnswer, MemoryStore, ResponseMessage, Store, User, UserId,
+};
+use anyhow::format_err;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{Client, StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TG_URL: &str = "https://api.telegram.org";
+
+const SEND_MESSAGE: &str = "sendMessage";
+const GET_ME: &str = "getMe";
+const SET_MY_COMMANDS: &str = "setMyCommands";
+const GET_MY_COMMANDS: &str = "getMyCommands";
+const DELETE_MY_COMMANDS: &str = "deleteMyCommands";
+const ANSWER_INLINE_QUERY: &str = "answerInlineQuery";
+const GET_CHAT: &str = "getChat";
+const GET_CHAT_MEMBER: &str = "getChatMember";
+const GET_FILE: &str = "getFile";
+const ANSWER_CALLBACK_QUERY: &str = "answerCallbackQuery";
+const APPROVE_CHAT_JOIN_REQUEST: &str = "approveChatJoinRequest";
+const DECLINE_CHAT_JOIN_REQUEST: &str = "declineChatJoinRequest";
+
+/// Bot API server that requests are sent to.
+#[derive(Debug, Clone)]
+pub struct ApiServer {
+    url: String,
+    test_environment: bool,
+    local: bool,
+}
+
+impl ApiServer {
+    /// Server at `url`, e.g. a self-hosted `telegram-bot-api` at `http://localhost:8081`.
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_owned(),
+            test_environment: false,
+            local: false,
+        }
+    }
+
+    /// Uses the test environment, where bots are created with the test @BotFather.
+    pub fn test_environment(mut self) -> Self {
+        self.test_environment = true;
+        self
+    }
+
+    /// The server runs with `--local`: files are read from the paths returned by `getFile`
+    /// instead of being downloaded, so the server must share the bot's filesystem.
+    pub fn local(mut self) -> Self {
+        self.local = true;
+        self
+    }
+
+    fn method_url(&self, token: &str, method: &str) -> String {
+        match self.test_environment {
+            true => format!("{}/{}/test/{}", self.url, token, method),
+            false => format!("{}/{}/{}", self.url, token, method),
+        }
+    }
+
+    fn file_url(&self, token: &str, file_path: &str) -> String {
+        match self.test_environment {
+            true => format!("{}/file/{}/test/{}", self.url, token, file_path),
+            false => format!("{}/file/{}/{}", self.url, token, file_path),
+        }
+    }
+}
+
+impl Default for ApiServer {
+    fn default() -> Self {
+        Self::new(TG_URL)
+    }
+}
+
+/// Bot API client, usable without incoming updates, e.g. from background jobs.
+///
+/// Clones share the connection pool and the rate limiter, so configure the client before
+/// cloning it. The client of a bot also signs and stores callback data of outgoing keyboards
+/// as set with `Bot::sign_callbacks` and `Bot::store_large_callbacks`.
+#[derive(Clone)]
+pub struct Api {
+    token: String,
+    server: ApiServer,
+    client: Client,
+    limiter: Option<Arc<RateLimiter>>,
+    retry: RetryPolicy,
+    store: Arc<dyn Store>,
+    callback_key: Option<CallbackKey>,
+    large_callback_ttl: Option<Duration>,
+}
+
+impl Api {
+    /// Client with the default [`RateLimit`] and [`RetryPolicy`].
+    pub fn new(token: String) -> Self {
+        Self {
+            token,
+            server: ApiServer::default(),
+            client: Client::new(),
+            limiter: Some(Arc::new(RateLimiter::new(RateLimit::default()))),
+            retry: RetryPolicy::default(),
+            store: Arc::new(MemoryStore::new()),
+            callback_key: None,
+            large_callback_ttl: None,
+        }
+    }
+
+    pub fn set_api_server(&mut self, server: ApiServer) {
+        self.server = server;
+    }
+
+    /// Paces `sendMessage` calls; `None` sends them as fast as possible.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.limiter = limit.map(|limit| Arc::new(RateLimiter::new(limit)));
+    }
+
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    pub(crate) fn store(&self) -> &dyn Store {
+        self.store.as_ref()
+    }
+
+    pub(crate) fn set_store(&mut self, store: Arc<dyn Store>) {
+        self.store = store;
+    }
+
+    pub(crate) fn callback_key(&self) -> Option<&CallbackKey> {
+        self.callback_key.as_ref()
+    }
+
+    pub(crate) fn set_callback_key(&mut self, key: CallbackKey) {
+        self.callback_key = Some(key);
+    }
+
+    pub(crate) fn set_large_callback_ttl(&mut self, ttl: Duration) {
+        self.large_callback_ttl = Some(ttl);
+    }
+
+    /// Prepares callback data of an outgoing keyboard: moves oversized payloads to the store
+    /// and signs the result.
+    pub(crate) async fn seal(&self, markup: Option<&mut InlineKeyboardMarkup>) -> Fallible<()> {
+        let Some(markup) = markup else {
+            return Ok(());
+        };
+
+        for data in markup.callback_data_mut() {
+            if let Some(ttl) = self.large_callback_ttl {
+                if data.is_oversized(self.callback_key.is_some()) {
+                    let key = random_key()?;
+                    let payload = data.store_as(&key);
+                    self.store
+                        .set(callback_store_key(&key), payload.into_bytes(), Some(ttl))
+                        .await?;
+                }
+            }
+
+            match &self.callback_key {
+                Some(key) => data.sign(key)?,
+                None => data.check_len()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `message`, signing and storing the callback data of its keyboard as the bot is
+    /// configured to.
+    pub async fn send_message(&self, mut message: ResponseMessage) -> Fallible<BotResponse> {
+        self.seal(message.reply_markup.as_mut()).await?;
+        self.send_sealed(message).await
+    }
+
+    /// Sends a message whose keyboard was already prepared with [`seal`](Self::seal).
+    pub(crate) async fn send_sealed(&self, message: ResponseMessage) -> Fallible<BotResponse> {
+        let response: ApiResponse<serde_json::Value> = self
+            .send(SEND_MESSAGE, Some(message.chat_id), &message)
+            .await?;
+        Ok(BotResponse {
+            ok: response.ok,
+            error_code: response.error_code,
+            description: response.description,
+        })
+    }
+
+    pub async fn get_me(&self) -> Fallible<User> {
+        self.request(GET_ME, &serde_json::json!({})).await
+    }
+
+    pub async fn get_chat(&self, chat_id: ChatId) -> Fallible<ChatFullInfo> {
+        self.request(GET_CHAT, &serde_json::json!({ "chat_id": chat_id }))
+            .await
+    }
+
+    pub async fn get_chat_member(&self, chat_id: ChatId, user_id: UserId) -> Fallible<ChatMember> {
+        let body = serde_json::json!({ "chat_id": chat_id, "user_id": user_id });
+        self.request(GET_CHAT_MEMBER, &body).await
+    }
+
+    /// Prepares a file sent to the bot for download, see [`download_file`](Self::download_file).
+    pub async fn get_file(&self, file_id: &str) -> Fallible<File> {
+        self.request(GET_FILE, &serde_json::json!({ "file_id": file_id }))
+            .await
+    }
+
+    /// Downloads a file returned by `get_file`, or reads it from disk in local mode.
+    pub async fn download_file(&self, file: &File) -> Fallible<Vec<u8>> {
+        let file_path = file
+            .file_path
+            .as_deref()
+            .ok_or_else(|| format_err!("File {} has no path", file.file_id))?;
+        if self.server.local {
+            return Ok(tokio::fs::read(file_path).await?);
+        }
+
+        let uri = Url::parse(&self.server.file_url(&self.token, file_path))?;
+        let response = self.client.get(uri).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Stops the loading indicator on a pressed button, optionally showing `text` as a toast.
+    pub async fn answer_callback_query(
+        &self,
+        callback_query_id: &str,
+        text: Option<&str>,
+    ) -> Fallible<bool> {
+        let body = AnswerCallbackQuery {
+            callback_query_id,
+            text,
+        };
+        self.request(ANSWER_CALLBACK_QUERY, &body).await
+    }
+
+    pub async fn approve_chat_join_request(
+        &self,
+        chat_id: ChatId,
+        user_id: UserId,
+    ) -> Fallible<bool> {
+        let body = serde_json::json!({ "chat_id": chat_id, "user_id": user_id });
+        self.request(APPROVE_CHAT_JOIN_REQUEST, &body).await
+    }
+
+    pub async fn decline_chat_join_request(
+        &self,
+        chat_id: ChatId,
+        user_id: UserId,
+    ) -> Fallible<bool> {
+        let body = serde_json::json!({ "chat_id": chat_id, "user_id": user_id });
+        self.request(DECLINE_CHAT_JOIN_REQUEST, &body).await
+    }
+
+    pub async fn set_my_commands(
+        &self,
+        commands: &[BotCommand],
+        scope: &BotCommandScope,
+        language_code: Option<&str>,
+    ) -> Fallible<bool> {
+        let body = CommandsRequest {
+            commands: Some(commands),
+            scope,
+            language_code,
+        };
+        self.request(SET_MY_COMMANDS, &body).await
+    }
+
+    pub async fn get_my_commands(
+        &self,
+        scope: &BotCommandScope,
+        language_code: Option<&str>,
+    ) -> Fallible<Vec<BotCommand>> {
+        let body = CommandsRequest {
+            commands: None,
+            scope,
+            language_code,
+        };
+        self.request(GET_MY_COMMANDS, &body).await
+    }
+
+    pub async fn delete_my_commands(
+        &self,
+        scope: &BotCommandScope,
+        language_code: Option<&str>,
+    ) -> Fallible<bool> {
+        let body = CommandsRequest {
+            commands: None,
+            scope,
+            language_code,
+        };
+        self.request(DELETE_MY_COMMANDS, &body).await
+    }
+
+    pub async fn answer_inline_query(
+        &self,
+        inline_query_id: &str,
+        answer: &InlineQueryAnswer,
+    ) -> Fallible<bool> {
+        let body = AnswerInlineQuery {
+            inline_query_id,
+            answer,
+        };
+        self.request(ANSWER_INLINE_QUERY, &body).await
+    }
+
+    /// Calls a Bot API method and unwraps the `result` field of a successful response.
+    async fn request<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: &str,
+        body: &B,
+    ) -> Fallible<T> {
+        match self.send(method, None, body).await? {
+            ApiResponse {
+                ok: true,
+                result: Some(result),
+                ..
+            } => Ok(result),
+            ApiResponse { description, .. } => Err(format_err!(
+                "{} failed: {}",
+                method,
+                description.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// Sends a request, waiting for the rate limiter when it goes to `chat_id` and retrying
+    /// it as the retry policy allows.
+    async fn send<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: &str,
+        chat_id: Option<ChatId>,
+        body: &B,
+    ) -> Fallible<ApiResponse<T>> {
+        let uri = Url::parse(&self.server.method_url(&self.token, method))?;
+        let mut attempt = 0;
+        loop {
+            if let (Some(limiter), Some(chat_id)) = (&self.limiter, chat_id) {
+                limiter.wait(chat_id).await;
+            }
+
+            let (err, delay) = match self.client.post(uri.clone()).json(body).send().await {
+                Ok(response) if response.status().is_server_error() => (
+                    format_err!("{} failed: {}", method, response.status()),
+                    self.retry.backoff(attempt),
+                ),
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    let response: ApiResponse<T> = response.json().await?;
+                    let retry_after = response
+                        .parameters
+                        .and_then(|parameters| parameters.retry_after)
+                        .unwrap_or_default();
+                    (
+                        format_err!(
+                            "{} failed: {}",
+                            method,
+                            response.description.unwrap_or_default()
+                        ),
+                        Duration::from_secs(retry_after),
+                    )
+                }
+                Ok(response) => return Ok(response.json().await?),
+                Err(err) if err.is_connect() => (err.into(), self.retry.backoff(attempt)),
+                Err(err) => return Err(err.into()),
+            };
+
+            if attempt >= self.retry.max_retries {
+                return Err(err);
+            }
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Store key of a callback payload that was too large to send.
+pub(crate) fn callback_store_key(key: &str) -> String {
+    format!("callback:{}", key)
+}
+
+/// Unguessable key for a stored callback payload.
+fn random_key() -> Fallible<String> {
+    let mut bytes = [0; 9];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BotResponse {
+    pub ok: bool,
+    /// HTTP-like code of a failed request, e.g. 403 when the user blocked the bot.
+    pub error_code: Option<u16>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    error_code: Option<u16>,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseParameters {
+    /// Seconds to wait before repeating a request that exceeded flood control.
+    retry_after: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct CommandsRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commands: Option<&'a [BotCommand]>,
+    scope: &'a BotCommandScope,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language_code: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct AnswerInlineQuery<'a> {
+    inline_query_id: &'a str,
+    #[serde(flatten)]
+    answer: &'a InlineQueryAnswer,
+}
+
+#[derive(Serialize)]
+struct AnswerCallbackQuery<'a> {
+    callback_query_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+}
@@ -0,0 +1,244 @@
+use crate::Fallible;
+use anyhow::format_err;
+use std::{fmt::Display, str::FromStr};
+
+/// Arguments that follow a command name, e.g. `BTC USDT` in `/price BTC USDT`.
+///
+/// Arguments are separated by whitespace; single or double quotes group words into one
+/// argument and a backslash escapes the next character. A quote that is never closed extends
+/// to the end of the text, so parsing never fails.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    raw: String,
+    items: Vec<String>,
+}
+
+impl Args {
+    pub fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+        let mut items = vec![];
+        let mut current = String::new();
+        let mut in_item = false;
+        let mut quote = None;
+        let mut chars = raw.chars();
+
+        while let Some(ch) = chars.next() {
+            match (ch, quote) {
+                ('\\', _) => {
+                    current.extend(chars.next());
+                    in_item = true;
+                }
+                ('"' | '\'', None) => {
+                    quote = Some(ch);
+                    in_item = true;
+                }
+                (ch, Some(open)) if ch == open => quote = None,
+                (ch, None) if ch.is_whitespace() => {
+                    if in_item {
+                        items.push(std::mem::take(&mut current));
+                        in_item = false;
+                    }
+                }
+                (ch, _) => {
+                    current.push(ch);
+                    in_item = true;
+                }
+            }
+        }
+
+        if in_item {
+            items.push(current);
+        }
+
+        Self {
+            raw: raw.to_owned(),
+            items,
+        }
+    }
+
+    /// The argument string as it was sent, without surrounding whitespace.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.items.get(idx).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().map(String::as_str)
+    }
+
+    /// Parses the argument at `idx`, failing if it is missing or malformed.
+    pub fn parse_at<T>(&self, idx: usize) -> Fallible<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self
+            .get(idx)
+            .ok_or_else(|| format_err!("Missing argument #{}", idx + 1))?;
+        value
+            .parse()
+            .map_err(|err| format_err!("Invalid argument `{}`: {}", value, err))
+    }
+
+    /// Parses the argument at `idx` if it is present.
+    pub fn parse_opt<T>(&self, idx: usize) -> Fallible<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.get(idx) {
+            Some(_) => self.parse_at(idx).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// All arguments starting at `idx`.
+    pub fn rest(&self, idx: usize) -> &[String] {
+        self.items.get(idx..).unwrap_or_default()
+    }
+}
+
+/// Typed view of command arguments, usually obtained with `#[derive(FromArgs)]`.
+///
+/// Fields are filled positionally. `Option` fields and fields marked `#[args(default)]` or
+/// `#[args(default = ...)]` may be omitted, and a last field marked `#[args(rest)]` takes every
+/// remaining argument through [`FromRest`].
+///
+/// ```
+/// use boteg::{Args, FromArgs};
+///
+/// #[derive(FromArgs)]
+/// struct Transfer {
+///     amount: u64,
+///     currency: String,
+///     to: Option<String>,
+/// }
+///
+/// let transfer = Transfer::from_args(&Args::parse("100 EUR alice"))?;
+/// assert_eq!(transfer.amount, 100);
+/// assert_eq!(transfer.to.as_deref(), Some("alice"));
+/// assert_eq!(Transfer::usage(), "<amount> <currency> [to]");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait FromArgs: Sized {
+    /// Argument list shown to the user when parsing fails, e.g. `<amount> <currency> [to]`.
+    fn usage() -> String;
+
+    fn from_args(args: &Args) -> Fallible<Self>;
+}
+
+impl FromArgs for Args {
+    fn usage() -> String {
+        "[args...]".to_owned()
+    }
+
+    fn from_args(args: &Args) -> Fallible<Self> {
+        Ok(args.clone())
+    }
+}
+
+/// Conversion of the trailing arguments into a single `#[args(rest)]` field.
+pub trait FromRest: Sized {
+    fn from_rest(items: &[String]) -> Fallible<Self>;
+}
+
+impl FromRest for String {
+    fn from_rest(items: &[String]) -> Fallible<Self> {
+        Ok(items.join(" "))
+    }
+}
+
+impl<T> FromRest for Vec<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn from_rest(items: &[String]) -> Fallible<Self> {
+        items
+            .iter()
+            .map(|item| {
+                item.parse()
+                    .map_err(|err| format_err!("Invalid argument `{}`: {}", item, err))
+            })
+            .collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a Args {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl IntoIterator for Args {
+    type Item = String;
+    type IntoIter = std::vec::IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+
+    fn items(raw: &str) -> Vec<String> {
+        Args::parse(raw).into_iter().collect()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(items("  BTC \t USDT "), ["BTC", "USDT"]);
+        assert_eq!(Args::parse("  BTC USDT ").raw(), "BTC USDT");
+    }
+
+    #[test]
+    fn empty_input_has_no_args() {
+        assert!(Args::parse("").is_empty());
+        assert!(Args::parse("   ").is_empty());
+    }
+
+    #[test]
+    fn double_quotes_group_words() {
+        assert_eq!(items(r#"send "a b" c"#), ["send", "a b", "c"]);
+    }
+
+    #[test]
+    fn single_quotes_group_words() {
+        assert_eq!(items("'x' 'y z'"), ["x", "y z"]);
+        assert_eq!(items(r#"'say "hi"'"#), [r#"say "hi""#]);
+    }
+
+    #[test]
+    fn empty_quotes_are_an_argument() {
+        assert_eq!(items(r#"a "" b"#), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn backslash_escapes_the_next_character() {
+        assert_eq!(items(r#"\"a b\""#), [r#""a"#, r#"b""#]);
+        assert_eq!(items(r"a\ b"), ["a b"]);
+        assert_eq!(items(r#""a\"b""#), [r#"a"b"#]);
+    }
+
+    #[test]
+    fn unclosed_quote_extends_to_the_end() {
+        assert_eq!(items(r#"a "b c"#), ["a", "b c"]);
+        assert_eq!(items("'"), [""]);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{Api, ChatId, Fallible, ResponseMessage, Store};
+use futures_core::Stream;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Code of a failed request to a chat that blocked the bot or that the bot was removed from.
+const FORBIDDEN: u16 = 403;
+
+/// One message sent to many chats, see [`send`](Self::send).
+pub struct Broadcast {
+    id: String,
+    message: ResponseMessage,
+    keep_progress_for: Duration,
+    on_outcome: Option<OutcomeFn>,
+}
+
+type OutcomeFn = Box<dyn Fn(ChatId, &Outcome, &Progress) + Send + Sync + 'static>;
+
+/// What happened to the broadcast in one chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Sent,
+    /// The chat blocked the bot; it is skipped by later broadcasts too.
+    Blocked,
+    /// The message was sent before a restart, or the chat blocked the bot earlier.
+    Skipped,
+    /// Sending failed; the chat is tried again when the broadcast is resumed.
+    Failed(String),
+}
+
+/// Counts of outcomes so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub sent: u64,
+    pub blocked: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+impl Progress {
+    pub fn total(&self) -> u64 {
+        self.sent + self.blocked + self.skipped + self.failed
+    }
+
+    fn add(&mut self, outcome: &Outcome) {
+        match outcome {
+            Outcome::Sent => self.sent += 1,
+            Outcome::Blocked => self.blocked += 1,
+            Outcome::Skipped => self.skipped += 1,
+            Outcome::Failed(_) => self.failed += 1,
+        }
+    }
+}
+
+impl Broadcast {
+    /// Broadcast of `message`, whose `chat_id` is replaced for every chat.
+    ///
+    /// Progress is kept in the store under `id`, so sending again with the same `id` after a
+    /// restart skips the chats that were already handled.
+    pub fn new<S: Into<String>>(id: S, message: ResponseMessage) -> Self {
+        Self {
+            id: id.into(),
+            message,
+            keep_progress_for: Duration::from_secs(7 * 24 * 60 * 60),
+            on_outcome: None,
+        }
+    }
+
+    /// How long the handled chats are remembered for resuming; a week by default.
+    pub fn keep_progress_for(mut self, ttl: Duration) -> Self {
+        self.keep_progress_for = ttl;
+        self
+    }
+
+    /// Called after every chat with its outcome and the progress so far.
+    pub fn on_outcome<F>(mut self, on_outcome: F) -> Self
+    where
+        F: Fn(ChatId, &Outcome, &Progress) + Send + Sync + 'static,
+    {
+        self.on_outcome = Some(Box::new(on_outcome));
+        self
+    }
+
+    /// Sends the message to every chat of `chats` one by one, paced by the rate limit of `api`.
+    ///
+    /// Only errors of the store stop the broadcast; failed sends are reported as outcomes.
+    pub async fn send<S>(&self, api: &Api, store: &dyn Store, chats: S) -> Fallible<Progress>
+    where
+        S: Stream<Item = ChatId>,
+    {
+        let mut chats = pin!(chats);
+        let mut progress = Progress::default();
+        while let Some(chat_id) = std::future::poll_fn(|cx| chats.as_mut().poll_next(cx)).await {
+            let outcome = self.send_to(api, store, chat_id).await?;
+            progress.add(&outcome);
+            if let Some(on_outcome) = &self.on_outcome {
+                on_outcome(chat_id, &outcome, &progress);
+            }
+        }
+        Ok(progress)
+    }
+
+    /// Same as [`send`](Self::send) for chats that are already at hand.
+    pub async fn send_all<I>(&self, api: &Api, store: &dyn Store, chats: I) -> Fallible<Progress>
+    where
+        I: IntoIterator<Item = ChatId>,
+        I::IntoIter: Unpin,
+    {
+        self.send(api, store, Iter(chats.into_iter())).await
+    }
+
+    async fn send_to(&self, api: &Api, store: &dyn Store, chat_id: ChatId) -> Fallible<Outcome> {
+        let handled_key = format!("broadcast:{}:{}", self.id, chat_id);
+        let blocked_key = blocked_key(chat_id);
+        if store.get(handled_key.clone()).await?.is_some()
+            || store.get(blocked_key.clone()).await?.is_some()
+        {
+            return Ok(Outcome::Skipped);
+        }
+
+        let message = ResponseMessage {
+            chat_id,
+            ..self.message.clone()
+        };
+        match api.send_message(message).await {
+            Ok(response) if response.ok => {
+                store
+                    .set(handled_key, vec![], Some(self.keep_progress_for))
+                    .await?;
+                Ok(Outcome::Sent)
+            }
+            Ok(response) if response.error_code == Some(FORBIDDEN) => {
+                store.set(blocked_key, vec![], None).await?;
+                Ok(Outcome::Blocked)
+            }
+            Ok(response) => Ok(Outcome::Failed(response.description.unwrap_or_default())),
+            Err(err) => Ok(Outcome::Failed(err.to_string())),
+        }
+    }
+}
+
+/// Store key of a chat that blocked the bot, removed when the bot is unblocked.
+pub(crate) fn blocked_key(chat_id: ChatId) -> String {
+    format!("blocked:{}", chat_id)
+}
+
+/// [`Stream`] of the items of an iterator.
+struct Iter<I>(I);
+
+impl<I: Iterator + Unpin> Stream for Iter<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.0.next())
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Number of update ids remembered by default.
+pub(crate) const DEFAULT_WINDOW: usize = 1000;
+
+/// The most recent update ids, used to recognize updates that Telegram delivers again.
+pub(crate) struct UpdateWindow {
+    capacity: usize,
+    order: VecDeque<u64>,
+    ids: HashSet<u64>,
+}
+
+impl UpdateWindow {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            ids: HashSet::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn contains(&self, update_id: u64) -> bool {
+        self.ids.contains(&update_id)
+    }
+
+    /// Remembers `update_id`, returning `false` when it is already in the window.
+    pub(crate) fn insert(&mut self, update_id: u64) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if !self.ids.insert(update_id) {
+            return false;
+        }
+
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(update_id);
+        true
+    }
+}
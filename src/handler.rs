@@ -0,0 +1,318 @@
+use crate::routes::Captures;
+use crate::{
+    Api, Args, Bot, CallbackData, ChatId, ChatJoinRequest, ChatMemberUpdated, ChosenInlineResult,
+    Contact, Contents, Document, Fallible, FromArgs, InlineQuery, Location, Media, Message,
+    PhotoSize, Sticker, Store, Update, User, Voice,
+};
+use anyhow::format_err;
+use serde::de::DeserializeOwned;
+use std::{fmt, future::Future, ops::Deref, pin::Pin, sync::Arc};
+
+pub(crate) type BoxFuture<T> = Pin<Box<dyn Future<Output = Fallible<T>> + Send + 'static>>;
+pub(crate) type HandlerFn<R> = Box<dyn Fn(Context) -> BoxFuture<R> + Send + Sync + 'static>;
+
+/// Everything known about the update being handled, the source of handler arguments.
+pub struct Context {
+    update: Arc<Update>,
+    bot: Arc<Bot>,
+    command: Option<String>,
+    args: Args,
+    callback_data: Option<CallbackData>,
+    captures: Option<Captures>,
+}
+
+impl Context {
+    pub(crate) fn new(update: Arc<Update>, bot: Arc<Bot>) -> Self {
+        Self {
+            update,
+            bot,
+            command: None,
+            args: Args::default(),
+            callback_data: None,
+            captures: None,
+        }
+    }
+
+    pub(crate) fn with_command(mut self, command: &str) -> Self {
+        self.command = Some(command.to_owned());
+        self
+    }
+
+    pub(crate) fn with_args(mut self, args: Args) -> Self {
+        self.args = args;
+        self
+    }
+
+    pub(crate) fn with_callback_data(mut self, callback_data: CallbackData) -> Self {
+        self.callback_data = Some(callback_data);
+        self
+    }
+
+    pub(crate) fn with_captures(mut self, captures: Option<Captures>) -> Self {
+        self.captures = captures;
+        self
+    }
+
+    pub fn update(&self) -> &Update {
+        &self.update
+    }
+
+    /// Name of the command being executed, without the leading `/`.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    pub fn args(&self) -> &Args {
+        &self.args
+    }
+
+    pub fn callback_data(&self) -> Option<&CallbackData> {
+        self.callback_data.as_ref()
+    }
+
+    /// Groups captured by the regex text route that matched the message.
+    pub fn captures(&self) -> Option<&Captures> {
+        self.captures.as_ref()
+    }
+
+    pub fn api(&self) -> &Api {
+        &self.bot.api
+    }
+
+    pub fn store(&self) -> &dyn Store {
+        self.bot.store()
+    }
+
+    pub fn message(&self) -> Option<&Message> {
+        self.update.message()
+    }
+
+    /// State set with `Bot::with_state`, if it is of type `S`.
+    pub fn state<S: Send + Sync + 'static>(&self) -> Option<Arc<S>> {
+        self.bot.state.clone()?.downcast().ok()
+    }
+}
+
+/// Handler argument that can be taken from the update being handled.
+pub trait FromUpdate: Sized {
+    fn from_update(ctx: &Context) -> Fallible<Self>;
+}
+
+/// Async function that takes any number of [`FromUpdate`] arguments.
+///
+/// Implemented for every `Fn(T1, ..., T8) -> impl Future<Output = Fallible<R>>`, so both
+/// `async fn` items and closures returning `async move` blocks can be registered directly.
+/// `T` is the tuple of argument types and `R` is the value the handler resolves to.
+pub trait Handler<T, R>: Send + Sync + 'static {
+    fn call(&self, ctx: Context) -> BoxFuture<R>;
+}
+
+pub(crate) fn boxed<T, R, H: Handler<T, R>>(handler: H) -> HandlerFn<R> {
+    Box::new(move |ctx| handler.call(ctx))
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, Fut, R, $($arg,)*> Handler<($($arg,)*), R> for F
+        where
+            F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Fallible<R>> + Send + 'static,
+            $($arg: FromUpdate,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, ctx: Context) -> BoxFuture<R> {
+                $(
+                    let $arg = match $arg::from_update(&ctx) {
+                        Ok(value) => value,
+                        Err(err) => return Box::pin(async move { Err(err) }),
+                    };
+                )*
+                Box::pin(self($($arg),*))
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(T1);
+impl_handler!(T1, T2);
+impl_handler!(T1, T2, T3);
+impl_handler!(T1, T2, T3, T4);
+impl_handler!(T1, T2, T3, T4, T5);
+impl_handler!(T1, T2, T3, T4, T5, T6);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+/// Error of an extractor that is reported to the user as is instead of "Got error".
+#[derive(Debug)]
+pub struct Rejection(pub String);
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Typed payload of the pressed button, see [`CallbackData::with_payload`].
+#[derive(Debug, Clone)]
+pub struct Payload<T>(pub T);
+
+impl<T: DeserializeOwned> FromUpdate for Payload<T> {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        CallbackData::from_update(ctx)?.payload().map(Payload)
+    }
+}
+
+impl<T: FromUpdate> FromUpdate for Option<T> {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        Ok(T::from_update(ctx).ok())
+    }
+}
+
+/// Shared state set with `Bot::with_state`, taken as `State(state): State<S>`.
+///
+/// Extraction fails when no state of type `S` is set on the bot.
+#[derive(Debug)]
+pub struct State<S>(pub Arc<S>);
+
+impl<S> Clone for State<S> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: Send + Sync + 'static> FromUpdate for State<S> {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        ctx.state().map(State).ok_or_else(|| {
+            format_err!(
+                "State of type {} is not set on the bot",
+                std::any::type_name::<S>()
+            )
+        })
+    }
+}
+
+impl FromUpdate for Update {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        Ok(ctx.update().clone())
+    }
+}
+
+/// Fails for buttons of messages sent in inline mode, which Telegram does not include; take an
+/// `Option<Message>` in callbacks of such buttons.
+impl FromUpdate for Message {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        ctx.message()
+            .cloned()
+            .ok_or_else(|| format_err!("Update has no message"))
+    }
+}
+
+impl FromUpdate for ChatId {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        ctx.update()
+            .chat_id()
+            .ok_or_else(|| format_err!("Update has no chat"))
+    }
+}
+
+impl FromUpdate for User {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        ctx.update()
+            .user()
+            .cloned()
+            .ok_or_else(|| format_err!("Update has no user"))
+    }
+}
+
+impl FromUpdate for Args {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        Ok(ctx.args().clone())
+    }
+}
+
+impl FromUpdate for CallbackData {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        ctx.callback_data()
+            .cloned()
+            .ok_or_else(|| format_err!("Update is not a callback query"))
+    }
+}
+
+impl FromUpdate for Api {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        Ok(ctx.api().clone())
+    }
+}
+
+impl FromUpdate for Media {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        Message::from_update(ctx)?
+            .media()
+            .ok_or_else(|| format_err!("Message has no media"))
+    }
+}
+
+macro_rules! impl_from_contents {
+    ($ty:ty, $($variant:ident)|+, $name:literal) => {
+        impl FromUpdate for $ty {
+            fn from_update(ctx: &Context) -> Fallible<Self> {
+                match &ctx.update().contents {
+                    $(Contents::$variant(value))|+ => Ok(value.clone()),
+                    _ => Err(format_err!("Update is not {}", $name)),
+                }
+            }
+        }
+    };
+}
+
+impl_from_contents!(InlineQuery, InlineQuery, "an inline query");
+impl_from_contents!(
+    ChosenInlineResult,
+    ChosenInlineResult,
+    "a chosen inline result"
+);
+impl_from_contents!(
+    ChatMemberUpdated,
+    MyChatMember | ChatMember,
+    "a chat member update"
+);
+impl_from_contents!(ChatJoinRequest, ChatJoinRequest, "a chat join request");
+
+macro_rules! impl_from_message_field {
+    ($ty:ty, $field:ident) => {
+        impl FromUpdate for $ty {
+            fn from_update(ctx: &Context) -> Fallible<Self> {
+                ctx.message()
+                    .and_then(|message| message.$field.clone())
+                    .ok_or_else(|| format_err!("Message has no {}", stringify!($field)))
+            }
+        }
+    };
+}
+
+impl_from_message_field!(Vec<PhotoSize>, photo);
+impl_from_message_field!(Document, document);
+impl_from_message_field!(Voice, voice);
+impl_from_message_field!(Location, location);
+impl_from_message_field!(Contact, contact);
+impl_from_message_field!(Sticker, sticker);
+
+/// Extraction used by `#[derive(FromArgs)]`: replies with the usage line when parsing fails.
+#[doc(hidden)]
+pub fn args_from_update<T: FromArgs>(ctx: &Context) -> Fallible<T> {
+    T::from_args(ctx.args()).map_err(|err| {
+        let command = ctx.command().unwrap_or_default();
+        Rejection(format!("{}\nUsage: /{} {}", err, command, T::usage())).into()
+    })
+}
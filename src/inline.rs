@@ -0,0 +1,217 @@
+use crate::{InlineKeyboardMarkup, Location, User};
+use serde::{Deserialize, Serialize};
+
+/// Incoming `@bot query` typed in any chat.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InlineQuery {
+    pub id: String,
+    pub from: User,
+    pub query: String,
+    /// Offset of the results to return, as set in [`InlineQueryAnswer::next_offset`].
+    pub offset: String,
+    pub chat_type: Option<String>,
+    pub location: Option<Location>,
+}
+
+/// Result of an inline query that was chosen by a user and sent to their chat partner.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChosenInlineResult {
+    pub result_id: String,
+    pub from: User,
+    pub location: Option<Location>,
+    pub inline_message_id: Option<String>,
+    pub query: String,
+}
+
+/// Answer to an inline query, see `answerInlineQuery`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryAnswer {
+    pub results: Vec<InlineQueryResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_time: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_personal: Option<bool>,
+    /// Offset the client sends back to request the next page; empty or `None` for the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InlineQueryResult {
+    Article(InlineQueryResultArticle),
+    Photo(InlineQueryResultPhoto),
+    Gif(InlineQueryResultGif),
+    Document(InlineQueryResultDocument),
+    #[serde(rename = "photo")]
+    CachedPhoto(InlineQueryResultCachedPhoto),
+    #[serde(rename = "gif")]
+    CachedGif(InlineQueryResultCachedGif),
+    #[serde(rename = "document")]
+    CachedDocument(InlineQueryResultCachedDocument),
+    #[serde(rename = "sticker")]
+    CachedSticker(InlineQueryResultCachedSticker),
+}
+
+impl InlineQueryResult {
+    pub(crate) fn reply_markup_mut(&mut self) -> Option<&mut InlineKeyboardMarkup> {
+        match self {
+            InlineQueryResult::Article(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::Photo(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::Gif(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::Document(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::CachedPhoto(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::CachedGif(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::CachedDocument(result) => result.reply_markup.as_mut(),
+            InlineQueryResult::CachedSticker(result) => result.reply_markup.as_mut(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InputTextMessageContent {
+    pub message_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultArticle {
+    pub id: String,
+    pub title: String,
+    pub input_message_content: InputTextMessageContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultPhoto {
+    pub id: String,
+    pub photo_url: String,
+    pub thumbnail_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photo_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photo_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultGif {
+    pub id: String,
+    pub gif_url: String,
+    pub thumbnail_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gif_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gif_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultDocument {
+    pub id: String,
+    pub title: String,
+    pub document_url: String,
+    /// Either `application/pdf` or `application/zip`.
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultCachedPhoto {
+    pub id: String,
+    pub photo_file_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultCachedGif {
+    pub id: String,
+    pub gif_file_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultCachedDocument {
+    pub id: String,
+    pub title: String,
+    pub document_file_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InlineQueryResultCachedSticker {
+    pub id: String,
+    pub sticker_file_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_message_content: Option<InputTextMessageContent>,
+}
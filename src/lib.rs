@@ -1,45 +1,135 @@
 use anyhow::{bail, format_err};
-use std::{future::Future, sync::Arc};
+use std::{
+    any::Any,
+    future::Future,
+    sync::{Arc, Mutex},
+};
 
 pub type Fallible<T> = anyhow::Result<T>;
 
+mod access;
+pub use crate::access::AccessPolicy;
+
+mod api;
+pub use crate::api::{Api, ApiServer, BotResponse};
+
+mod args;
+pub use crate::args::{Args, FromArgs, FromRest};
+pub use boteg_derive::FromArgs;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::handler::args_from_update;
+    pub use anyhow::{bail, format_err};
+}
+
+mod broadcast;
+pub use crate::broadcast::{Broadcast, Outcome, Progress};
+
+mod dedupe;
+use crate::dedupe::UpdateWindow;
+
+mod handler;
+use crate::handler::HandlerFn;
+pub use crate::handler::{Context, FromUpdate, Handler, Payload, Rejection, State};
+
+mod inline;
+pub use crate::inline::{
+    ChosenInlineResult, InlineQuery, InlineQueryAnswer, InlineQueryResult,
+    InlineQueryResultArticle, InlineQueryResultCachedDocument, InlineQueryResultCachedGif,
+    InlineQueryResultCachedPhoto, InlineQueryResultCachedSticker, InlineQueryResultDocument,
+    InlineQueryResultGif, InlineQueryResultPhoto, InputTextMessageContent,
+};
+
+mod members;
+pub use crate::members::{
+    ChatInviteLink, ChatJoinRequest, ChatMember, ChatMemberAdministrator, ChatMemberBanned,
+    ChatMemberLeft, ChatMemberMember, ChatMemberOwner, ChatMemberRestricted, ChatMemberUpdated,
+    JoinRequestDecision,
+};
+
 mod messages;
+use crate::messages::CallbackKey;
 pub use crate::messages::{
-    CallbackData, Contents, InlineKeyboardButton, InlineKeyboardMarkup, Message, ResponseMessage,
-    Update,
+    BotCommand, BotCommandScope, CallbackData, Chat, ChatFullInfo, ChatId, ChatKind, ChatLocation,
+    ChatPhoto, Contact, Contents, Document, File, InlineKeyboardButton, InlineKeyboardMarkup,
+    Location, Media, Message, PhotoSize, ResponseMessage, Sticker, Update, User, UserId, Voice,
 };
+use hmac::Mac;
 use std::borrow::Cow;
 use std::{
     net::SocketAddr,
-    pin::Pin,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-mod sender;
-use axum::extract::State;
+mod queue;
+use crate::queue::ChatQueues;
+pub use crate::queue::Concurrency;
+
+mod routes;
+use crate::routes::TextRoute;
+pub use crate::routes::{Captures, TextPattern};
+
+mod scheduler;
+use crate::scheduler::Job;
+pub use crate::scheduler::{JobContext, Schedule};
+
+mod store;
+pub use crate::store::{MemoryStore, Store, StoreFuture};
+
+mod throttle;
+pub use crate::throttle::{RateLimit, RetryPolicy};
+
+#[cfg(feature = "testing")]
+pub mod testing;
 use axum::http::StatusCode;
 use axum::routing::post;
 use axum::{Json, Router};
 #[cfg(feature = "tls")]
 use axum_server::tls_rustls::RustlsConfig;
-use sender::Sender;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 #[cfg(feature = "tls")]
 use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::watch;
 
 type CommandRef = AtomicUsize;
-type Fut = Pin<Box<dyn Future<Output = Fallible<ResponseMessage>> + Send + 'static>>;
-type CallbackCommandFn = Box<dyn Fn(Message, Option<u64>) -> Fut + Send + Sync + 'static>;
-type CommandFn = Box<dyn Fn(Message) -> Fut + Send + Sync + 'static>;
+type CommandFn = HandlerFn<ResponseMessage>;
+type InlineQueryFn = HandlerFn<InlineQueryAnswer>;
+type ChatMemberFn = HandlerFn<Option<ResponseMessage>>;
+type ChatJoinRequestFn = HandlerFn<JoinRequestDecision>;
+type ChosenInlineResultFn = HandlerFn<()>;
 
 pub struct Bot {
     commands: Vec<Command>,
     inline_commands: HashMap<Cow<'static, str>, Command>,
+    text_routes: Vec<TextRoute>,
     current_command: CommandRef,
     enabled_current_command: bool,
-    callbacks: HashMap<Cow<'static, str>, CallbackCommandFn>,
+    callbacks: HashMap<Cow<'static, str>, CommandFn>,
+    media_handlers: MediaHandlers,
+    default_handler: Option<CommandFn>,
+    unknown_command_handler: Option<CommandFn>,
+    unknown_callback_handler: Option<CommandFn>,
+    inline_query_handler: Option<InlineQueryFn>,
+    chosen_inline_result_handler: Option<ChosenInlineResultFn>,
+    channel_post_handler: Option<CommandFn>,
+    edited_channel_post_handler: Option<CommandFn>,
+    my_chat_member_handler: Option<ChatMemberFn>,
+    chat_member_handler: Option<ChatMemberFn>,
+    chat_join_request_handler: Option<ChatJoinRequestFn>,
+    menu: Vec<MenuEntry>,
+    jobs: Vec<Job>,
+    admins: HashSet<UserId>,
+    access_policy: Option<AccessPolicy>,
+    denied_reply: Option<String>,
+    update_window: Mutex<UpdateWindow>,
+    update_id_ttl: Option<Duration>,
+    queues: ChatQueues,
+    state: Option<Arc<dyn Any + Send + Sync>>,
+    username: Option<String>,
     addr: SocketAddr,
-    sender: Sender,
+    api: Api,
     #[cfg(feature = "tls")]
     cert: Option<PathBuf>,
     #[cfg(feature = "tls")]
@@ -52,11 +142,33 @@ impl Bot {
         Ok(Self {
             commands: vec![],
             inline_commands: HashMap::default(),
-            current_command: AtomicUsize::new(0),
+            text_routes: vec![],
+            current_command: AtomicUsize::new(NO_COMMAND),
             enabled_current_command: false,
             callbacks: HashMap::new(),
+            media_handlers: MediaHandlers::default(),
+            default_handler: None,
+            unknown_command_handler: None,
+            unknown_callback_handler: None,
+            inline_query_handler: None,
+            chosen_inline_result_handler: None,
+            channel_post_handler: None,
+            edited_channel_post_handler: None,
+            my_chat_member_handler: None,
+            chat_member_handler: None,
+            chat_join_request_handler: None,
+            menu: vec![],
+            jobs: vec![],
+            admins: HashSet::new(),
+            access_policy: None,
+            denied_reply: None,
+            update_window: Mutex::new(UpdateWindow::new(dedupe::DEFAULT_WINDOW)),
+            update_id_ttl: None,
+            queues: ChatQueues::new(Concurrency::default()),
+            state: None,
+            username: None,
             addr: addr.into(),
-            sender: Sender::new(token),
+            api: Api::new(token),
         })
     }
 
@@ -70,38 +182,69 @@ impl Bot {
         Ok(Self {
             commands: vec![],
             inline_commands: HashMap::default(),
-            current_command: AtomicUsize::new(0),
+            text_routes: vec![],
+            current_command: AtomicUsize::new(NO_COMMAND),
             enabled_current_command: false,
             callbacks: HashMap::new(),
+            media_handlers: MediaHandlers::default(),
+            default_handler: None,
+            unknown_command_handler: None,
+            unknown_callback_handler: None,
+            inline_query_handler: None,
+            chosen_inline_result_handler: None,
+            channel_post_handler: None,
+            edited_channel_post_handler: None,
+            my_chat_member_handler: None,
+            chat_member_handler: None,
+            chat_join_request_handler: None,
+            menu: vec![],
+            jobs: vec![],
+            admins: HashSet::new(),
+            access_policy: None,
+            denied_reply: None,
+            update_window: Mutex::new(UpdateWindow::new(dedupe::DEFAULT_WINDOW)),
+            update_id_ttl: None,
+            queues: ChatQueues::new(Concurrency::default()),
+            state: None,
+            username: None,
             addr: addr.into(),
-            sender: Sender::new(token),
+            api: Api::new(token),
             cert,
             key,
         })
     }
 
-    pub fn add_command_static<F: Fn(Message) -> Fut + Send + Sync + 'static>(
+    /// Shares `state` with all handlers, which receive it with the [`State`] extractor.
+    ///
+    /// The bot keeps a single state: calling this again replaces the previous one, even if it
+    /// is of another type.
+    pub fn with_state<S: Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.state = Some(Arc::new(state));
+        self
+    }
+
+    pub fn add_command_static<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: &'static str,
-        cb: F,
+        handler: H,
     ) {
-        if let Err(err) = self.add_command(Cow::Borrowed(name), cb) {
+        if let Err(err) = self.add_command(Cow::Borrowed(name), handler) {
             panic!("{:?}", err);
         }
     }
 
-    pub fn add_command_dynamic<F: Fn(Message) -> Fut + Send + Sync + 'static>(
+    pub fn add_command_dynamic<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: String,
-        cb: F,
+        handler: H,
     ) -> Fallible<()> {
-        self.add_command(Cow::Owned(name), cb)
+        self.add_command(Cow::Owned(name), handler)
     }
 
-    fn add_command<F: Fn(Message) -> Fut + Send + Sync + 'static>(
+    fn add_command<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: Cow<'static, str>,
-        cb: F,
+        handler: H,
     ) -> Fallible<()> {
         if self.commands.iter().any(|command| command.name == name) {
             bail!("Command with name: `{}` already exists", name);
@@ -109,33 +252,242 @@ impl Bot {
 
         self.commands.push(Command {
             name,
-            cb: Box::new(cb),
+            cb: handler::boxed(handler),
+            access: None,
+        });
+        Ok(())
+    }
+
+    /// Signs the callback data of every outgoing keyboard with `secret`.
+    ///
+    /// Callback queries whose signature does not verify are only acknowledged, without a reply.
+    pub fn sign_callbacks<K: AsRef<[u8]>>(&mut self, secret: K) {
+        let key =
+            CallbackKey::new_from_slice(secret.as_ref()).expect("HMAC accepts keys of any length");
+        self.api.set_callback_key(key);
+    }
+
+    /// Keeps payloads of large callbacks in the bot store for `ttl`, sending only a short key.
+    ///
+    /// Pressing a button whose payload has expired shows a "This button has expired" notice.
+    pub fn store_large_callbacks(&mut self, ttl: Duration) {
+        self.api.set_large_callback_ttl(ttl);
+    }
+
+    /// Sets how many recent update ids are remembered to acknowledge updates that Telegram
+    /// delivers again without handling them twice; 1000 by default, 0 turns this off.
+    pub fn remember_updates(&mut self, window: usize) {
+        self.update_window = Mutex::new(UpdateWindow::new(window));
+    }
+
+    /// Also keeps handled update ids in the bot store for `ttl`, so that redelivered updates
+    /// are recognized after a restart or by another instance sharing the store.
+    pub fn persist_update_ids(&mut self, ttl: Duration) {
+        self.update_id_ttl = Some(ttl);
+    }
+
+    /// Replaces the default limits on handling updates concurrently.
+    ///
+    /// Refused updates are answered with `503 Service Unavailable`, so Telegram delivers them
+    /// again later.
+    pub fn set_concurrency(&mut self, concurrency: Concurrency) {
+        self.queues = ChatQueues::new(concurrency);
+    }
+
+    pub fn store(&self) -> &dyn Store {
+        self.api.store()
+    }
+
+    /// Replaces the default [`MemoryStore`] used for bot state.
+    pub fn set_store<S: Store>(&mut self, store: S) {
+        self.api.set_store(Arc::new(store));
+    }
+
+    /// Replaces the handler for text that matches no route while no command is selected, see
+    /// `enable_current_command`, and for media that has no handler of its own.
+    ///
+    /// Such messages are ignored when this handler is not set.
+    pub fn set_default_handler<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.default_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for `/commands` that are not registered.
+    ///
+    /// Such commands are ignored when this handler is not set.
+    pub fn on_unknown_command<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.unknown_command_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for buttons whose callback is not registered.
+    ///
+    /// When this handler is not set, such presses are only acknowledged.
+    pub fn on_unknown_callback<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.unknown_callback_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for inline queries; its answer is sent with `answerInlineQuery`.
+    pub fn on_inline_query<T, H: Handler<T, InlineQueryAnswer>>(&mut self, handler: H) {
+        self.inline_query_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for inline results chosen by users.
+    ///
+    /// Telegram only sends these when inline feedback is enabled for the bot in @BotFather.
+    pub fn on_chosen_inline_result<T, H: Handler<T, ()>>(&mut self, handler: H) {
+        self.chosen_inline_result_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for new posts in channels where the bot is an administrator.
+    pub fn on_channel_post<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.channel_post_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for edited posts in channels where the bot is an administrator.
+    pub fn on_edited_channel_post<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.edited_channel_post_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler called when the bot is added to, removed from or promoted in a chat.
+    pub fn on_my_chat_member<T, H: Handler<T, Option<ResponseMessage>>>(&mut self, handler: H) {
+        self.my_chat_member_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for membership changes of other users.
+    ///
+    /// The bot must be an administrator and `chat_member` must be listed in the webhook
+    /// `allowed_updates` for Telegram to send these.
+    pub fn on_chat_member<T, H: Handler<T, Option<ResponseMessage>>>(&mut self, handler: H) {
+        self.chat_member_handler = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler deciding on requests to join chats the bot administers.
+    pub fn on_chat_join_request<T, H: Handler<T, JoinRequestDecision>>(&mut self, handler: H) {
+        self.chat_join_request_handler = Some(handler::boxed(handler));
+    }
+
+    /// Shows a registered command in the "/" menu of every chat.
+    pub fn describe_command<D: Into<String>>(
+        &mut self,
+        name: &str,
+        description: D,
+    ) -> Fallible<()> {
+        self.describe_command_for(name, description, BotCommandScope::Default, None)
+    }
+
+    /// Shows a registered command in the "/" menu for the given scope and user language.
+    ///
+    /// Menus are sent with `setMyCommands` on startup, one call per scope and language.
+    pub fn describe_command_for<D: Into<String>>(
+        &mut self,
+        name: &str,
+        description: D,
+        scope: BotCommandScope,
+        language_code: Option<&str>,
+    ) -> Fallible<()> {
+        if !self.commands.iter().any(|command| command.name == name) {
+            bail!("Command with name: `{}` not found", name);
+        }
+
+        self.menu.push(MenuEntry {
+            command: BotCommand {
+                command: name.to_owned(),
+                description: description.into(),
+            },
+            scope,
+            language_code: language_code.map(str::to_owned),
         });
         Ok(())
     }
 
-    pub fn add_command_inline_static<F: Fn(Message) -> Fut + Send + Sync + 'static>(
+    /// Sets the users that pass every access policy.
+    pub fn set_admins<I: IntoIterator<Item = UserId>>(&mut self, admins: I) {
+        self.admins = admins.into_iter().collect();
+    }
+
+    /// Restricts every update the bot handles; denied updates are dropped or answered with
+    /// the text set by `reply_when_denied`.
+    pub fn set_access_policy(&mut self, policy: AccessPolicy) {
+        self.access_policy = Some(policy);
+    }
+
+    /// Restricts a registered command on top of the bot policy, together with the inline
+    /// command of the same name if there is one.
+    pub fn restrict_command(&mut self, name: &str, policy: AccessPolicy) -> Fallible<()> {
+        let commands = self
+            .commands
+            .iter_mut()
+            .filter(|command| command.name == name)
+            .chain(self.inline_commands.get_mut(name));
+        let mut found = false;
+        for command in commands {
+            command.access = Some(policy.clone());
+            found = true;
+        }
+
+        if !found {
+            bail!("Command with name: `{}` not found", name);
+        }
+        Ok(())
+    }
+
+    /// Answers denied commands, messages and button presses with `text` instead of ignoring
+    /// them.
+    pub fn reply_when_denied<S: Into<String>>(&mut self, text: S) {
+        self.denied_reply = Some(text.into());
+    }
+
+    /// Runs `job` on `schedule` while the bot is running.
+    ///
+    /// A run that fails is skipped; the job runs again at the next scheduled time.
+    pub fn schedule<F, Fut>(&mut self, schedule: Schedule, job: F)
+    where
+        F: Fn(JobContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Fallible<()>> + Send + 'static,
+    {
+        self.jobs.push(Job::new(schedule, job));
+    }
+
+    /// Client of the bot, cloneable for sending messages outside of handlers.
+    pub fn api(&self) -> &Api {
+        &self.api
+    }
+
+    /// Sends Bot API requests to `server` instead of `https://api.telegram.org`.
+    pub fn set_api_server(&mut self, server: ApiServer) {
+        self.api.set_api_server(server);
+    }
+
+    /// Paces outgoing messages, see [`Api::set_rate_limit`].
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.api.set_rate_limit(limit);
+    }
+
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.api.set_retry_policy(retry);
+    }
+
+    pub fn add_command_inline_static<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: &'static str,
-        cb: F,
+        handler: H,
     ) {
-        if let Err(err) = self.add_command_inline(Cow::Borrowed(name), cb) {
+        if let Err(err) = self.add_command_inline(Cow::Borrowed(name), handler) {
             panic!("{:?}", err);
         }
     }
 
-    pub fn add_command_inline_dynamic<F: Fn(Message) -> Fut + Send + Sync + 'static>(
+    pub fn add_command_inline_dynamic<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: String,
-        cb: F,
+        handler: H,
     ) -> Fallible<()> {
-        self.add_command_inline(Cow::Owned(name), cb)
+        self.add_command_inline(Cow::Owned(name), handler)
     }
 
-    fn add_command_inline<F: Fn(Message) -> Fut + Send + Sync + 'static>(
+    fn add_command_inline<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: Cow<'static, str>,
-        cb: F,
+        handler: H,
     ) -> Fallible<()> {
         if self.inline_commands.contains_key(&name) {
             bail!("Inline command with name: `{}` already exists", &name);
@@ -145,51 +497,284 @@ impl Bot {
             name.clone(),
             Command {
                 name,
-                cb: Box::new(cb),
+                cb: handler::boxed(handler),
+                access: None,
             },
         );
         Ok(())
     }
 
+    /// Adds a route for text messages that are not an exact inline command.
+    ///
+    /// Routes are tried in the order they were added, so register the more specific ones
+    /// first; messages matched by none of them go to the selected command or the default
+    /// handler.
+    pub fn add_text_route<T, H: Handler<T, ResponseMessage>>(
+        &mut self,
+        pattern: TextPattern,
+        handler: H,
+    ) {
+        self.text_routes.push(TextRoute {
+            pattern,
+            cb: handler::boxed(handler),
+        });
+    }
+
+    /// Lets users select a current command: `/command` without arguments selects it instead of
+    /// calling its handler, following plain text goes to it and `/current` shows it.
+    ///
+    /// Otherwise `/command` without arguments calls the handler with empty [`Args`].
     pub fn enable_current_command(&mut self) {
         self.enabled_current_command = true;
     }
 
-    pub fn add_callback_static<F: Fn(Message, Option<u64>) -> Fut + Send + Sync + 'static>(
+    /// Registers a callback for buttons built with `CallbackData::with_payload(name, &payload)`.
+    ///
+    /// Use the [`Payload`] extractor to receive the decoded payload.
+    pub fn add_callback_static<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: &'static str,
-        cb: F,
+        handler: H,
     ) {
-        if let Err(err) = self.add_callback(Cow::Borrowed(name), cb) {
+        if let Err(err) = self.add_callback(Cow::Borrowed(name), handler) {
             panic!("{:?}", err);
         }
     }
 
-    pub fn add_callback_dynamic<F: Fn(Message, Option<u64>) -> Fut + Send + Sync + 'static>(
+    pub fn add_callback_dynamic<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: String,
-        cb: F,
+        handler: H,
     ) -> Fallible<()> {
-        self.add_callback(Cow::Owned(name), cb)
+        self.add_callback(Cow::Owned(name), handler)
     }
 
-    fn add_callback<F: Fn(Message, Option<u64>) -> Fut + Send + Sync + 'static>(
+    fn add_callback<T, H: Handler<T, ResponseMessage>>(
         &mut self,
         name: Cow<'static, str>,
-        cb: F,
+        handler: H,
     ) -> Fallible<()> {
         if self.inline_commands.contains_key(&name) {
             bail!("Inline command with name: `{}` already exists", &name);
         }
 
-        self.callbacks.insert(name, Box::new(cb));
+        self.callbacks.insert(name, handler::boxed(handler));
         Ok(())
     }
 
-    #[cfg(feature = "tls")]
+    /// Replaces the handler for messages carrying a photo.
+    pub fn on_photo<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.photo = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for messages carrying a document.
+    pub fn on_document<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.document = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for voice messages.
+    pub fn on_voice<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.voice = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for shared locations.
+    pub fn on_location<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.location = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for shared contacts.
+    pub fn on_contact<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.contact = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for stickers.
+    pub fn on_sticker<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.sticker = Some(handler::boxed(handler));
+    }
+
+    /// Replaces the handler for any non-text message that has no more specific handler.
+    pub fn on_media<T, H: Handler<T, ResponseMessage>>(&mut self, handler: H) {
+        self.media_handlers.any = Some(handler::boxed(handler));
+    }
+
+    /// Sets the bot username used to recognize `/command@username` in group chats.
+    ///
+    /// When not set, the username is requested with `getMe` on startup.
+    pub fn set_username<S: Into<String>>(&mut self, username: S) {
+        self.username = Some(username.into());
+    }
+
+    /// Remembers `update_id`, returning `false` when the update was already handled.
+    ///
+    /// Nothing is remembered when this fails, so the update is handled when delivered again.
+    async fn is_new_update(&self, update_id: u64) -> Fallible<bool> {
+        if self.update_window.lock().unwrap().contains(update_id) {
+            return Ok(false);
+        }
+
+        if let Some(ttl) = self.update_id_ttl {
+            let is_new = self
+                .store()
+                .set_if_absent(update_key(update_id), vec![], Some(ttl))
+                .await?;
+            if !is_new {
+                self.update_window.lock().unwrap().insert(update_id);
+                return Ok(false);
+            }
+        }
+        Ok(self.update_window.lock().unwrap().insert(update_id))
+    }
+
+    async fn is_allowed(&self, policy: Option<&AccessPolicy>, update: &Update) -> Fallible<bool> {
+        let Some(policy) = policy else {
+            return Ok(true);
+        };
+        let user_id = update.user().map(|user| user.id);
+        if user_id.is_some_and(|user_id| self.admins.contains(&user_id)) {
+            return Ok(true);
+        }
+
+        let chat_id = update.chat_id();
+        if !policy.allows(user_id, chat_id) {
+            return Ok(false);
+        }
+        match (policy.requires_chat_admin(), chat_id, user_id) {
+            (false, _, _) => Ok(true),
+            (true, Some(chat_id), Some(user_id)) => self.is_chat_admin(chat_id, user_id).await,
+            (true, _, _) => Ok(false),
+        }
+    }
+
+    async fn is_chat_admin(&self, chat_id: ChatId, user_id: UserId) -> Fallible<bool> {
+        let key = format!("chat_admin:{}:{}", chat_id, user_id);
+        if let Some(value) = self.store().get(key.clone()).await? {
+            return Ok(value == [1]);
+        }
+
+        let is_admin = self
+            .api
+            .get_chat_member(chat_id, user_id)
+            .await?
+            .is_administrator();
+        self.store()
+            .set(key, vec![is_admin as u8], Some(CHAT_ADMIN_TTL))
+            .await?;
+        Ok(is_admin)
+    }
+
+    async fn deny(&self, update: &Update) -> Fallible<Option<ResponseMessage>> {
+        let text = self.denied_reply.as_deref();
+        match &update.contents {
+            Contents::CallbackMessage(callback_message) => {
+                self.api
+                    .answer_callback_query(&callback_message.id, text)
+                    .await?;
+                Ok(None)
+            }
+            Contents::Command(_) | Contents::Message(_) => {
+                Ok(text
+                    .zip(update.chat_id())
+                    .map(|(text, chat_id)| ResponseMessage {
+                        chat_id,
+                        text: text.to_owned(),
+                        parse_mode: None,
+                        reply_markup: None,
+                    }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn current_command(&self) -> Option<&Command> {
+        self.commands
+            .get(self.current_command.load(Ordering::Relaxed))
+    }
+
+    async fn resolve_username(&mut self) -> Fallible<()> {
+        if self.username.is_none() {
+            self.username = self.api.get_me().await?.username;
+        }
+        Ok(())
+    }
+
+    async fn sync_commands(&self) -> Fallible<()> {
+        let mut menus: Vec<(&BotCommandScope, Option<&str>, Vec<BotCommand>)> = vec![];
+        for entry in &self.menu {
+            let language_code = entry.language_code.as_deref();
+            match menus
+                .iter_mut()
+                .find(|(scope, language, _)| *scope == &entry.scope && *language == language_code)
+            {
+                Some((_, _, commands)) => commands.push(entry.command.clone()),
+                None => menus.push((&entry.scope, language_code, vec![entry.command.clone()])),
+            }
+        }
+
+        for (scope, language_code, commands) in menus {
+            self.api
+                .set_my_commands(&commands, scope, language_code)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Commands addressed to another bot with `/command@other_bot` must be ignored.
+    fn is_addressed_to_me(&self, addressee: Option<&str>) -> bool {
+        match (addressee, &self.username) {
+            (Some(addressee), Some(username)) => addressee.eq_ignore_ascii_case(username),
+            _ => true,
+        }
+    }
+
+    /// Startup calls made before serving updates.
+    async fn prepare(&mut self) -> Fallible<()> {
+        self.resolve_username().await?;
+        self.sync_commands().await
+    }
+
+    fn router(self: Arc<Self>) -> Router {
+        Router::new().route("/", post(handle)).with_state(self)
+    }
+
+    /// Serves updates and runs scheduled jobs until Ctrl+C.
     pub async fn run(self) -> Fallible<()> {
-        let addr = self.addr;
+        self.run_until(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+    }
+
+    /// Serves updates and runs scheduled jobs until `shutdown` completes.
+    ///
+    /// After that no new updates are accepted and no jobs are started; the call returns once
+    /// the ones in progress have finished.
+    pub async fn run_until<F>(mut self, shutdown: F) -> Fallible<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.prepare().await?;
+        let bot = Arc::new(self);
 
+        let (stop, stopped) = watch::channel(false);
+        let jobs = scheduler::start(&bot, &stopped);
+        let served = bot
+            .serve(async move {
+                shutdown.await;
+                let _ = stop.send(true);
+            })
+            .await;
+
+        for job in jobs {
+            let _ = job.await;
+        }
+        served
+    }
+
+    #[cfg(feature = "tls")]
+    async fn serve<F>(self: Arc<Self>, shutdown: F) -> Fallible<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
         let config = match (&self.cert, &self.key) {
             (Some(cert), Some(key)) => {
                 RustlsConfig::from_pem_file(PathBuf::from(cert), PathBuf::from(key)).await?
@@ -207,110 +792,338 @@ impl Bot {
             }
         };
 
-        let bot = Arc::new(self);
+        let handle = axum_server::Handle::new();
+        let server_handle = handle.clone();
+        // Aborting the task on a server error also drops the stop signal of the jobs.
+        let shutdown = tokio::spawn(async move {
+            shutdown.await;
+            server_handle.graceful_shutdown(None);
+        });
 
-        let app = Router::new().route("/", post(handle)).with_state(bot);
-        axum_server::bind_rustls(addr, config)
-            .serve(app.into_make_service())
-            .await?;
+        let addr = self.addr;
+        let served = axum_server::bind_rustls(addr, config)
+            .handle(handle)
+            .serve(self.router().into_make_service())
+            .await;
+        shutdown.abort();
 
-        Ok(())
+        Ok(served?)
     }
 
     #[cfg(not(feature = "tls"))]
-    pub async fn run(self) -> Fallible<()> {
-        let addr = self.addr;
-        let bot = Arc::new(self);
-
-        let app = Router::new().route("/", post(handle)).with_state(bot);
-
-        let listener = tokio::net::TcpListener::bind(addr).await?;
-        axum::serve(listener, app).await?;
+    async fn serve<F>(self: Arc<Self>, shutdown: F) -> Fallible<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind(self.addr).await?;
+        axum::serve(listener, self.router())
+            .with_graceful_shutdown(shutdown)
+            .await?;
 
         Ok(())
     }
 }
 
 async fn handle(
-    State(bot): State<Arc<Bot>>,
+    axum::extract::State(bot): axum::extract::State<Arc<Bot>>,
     Json(update): Json<Update>,
 ) -> Result<Json<()>, StatusCode> {
-    let chat_id = update.chat_id().expect("Expecting chat_id");
-    let body = dispatch(bot.clone(), update).await.unwrap_or_else(|_err| {
+    let Some(_turn) = bot.queues.enter(update.chat_id()).await else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    match bot.is_new_update(update.update_id).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(Json(())),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+
+    let chat_id = update.chat_id();
+    let body = match dispatch(bot.clone(), update).await {
+        Ok(Some(mut body)) => bot
+            .api
+            .seal(body.reply_markup.as_mut())
+            .await
+            .map(|()| Some(body)),
+        other => other,
+    };
+    let body = body.unwrap_or_else(|err| {
         //TODO log
-        ResponseMessage {
+        let text = match err.downcast::<Rejection>() {
+            Ok(rejection) => rejection.0,
+            Err(_err) => "Got error".to_owned(),
+        };
+        chat_id.map(|chat_id| ResponseMessage {
             chat_id,
-            text: "Got error".to_owned(),
+            text,
             parse_mode: None,
             reply_markup: None,
-        }
+        })
     });
-    match bot.sender.send_message(body).await {
-        Ok(response) if response.ok => Ok(Json(())),
-        _ => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    }
+    let Some(body) = body else {
+        return Ok(Json(()));
+    };
+    // The update stays remembered even when the reply is not delivered: the handler has run
+    // and its side effects must not be repeated by a redelivery.
+    //TODO log
+    let _ = bot.api.send_sealed(body).await;
+    Ok(Json(()))
 }
 
-async fn dispatch(bot: Arc<Bot>, update: Update) -> Fallible<ResponseMessage> {
-    let chat_id = update.chat_id();
+async fn dispatch(bot: Arc<Bot>, update: Update) -> Fallible<Option<ResponseMessage>> {
+    let update = Arc::new(update);
+    let ctx = Context::new(update.clone(), bot.clone());
+    // Unblocks the chat for broadcasts even when whoever added the bot may not use it.
+    if let Contents::MyChatMember(updated) = &update.contents {
+        if updated.new_chat_member.is_member() {
+            bot.store()
+                .remove(broadcast::blocked_key(updated.chat.id))
+                .await?;
+        }
+    }
+    if !bot.is_allowed(bot.access_policy.as_ref(), &update).await? {
+        return bot.deny(&update).await;
+    }
 
-    let body = match update.contents {
+    let body = match &update.contents {
         Contents::CallbackMessage(callback_message) => {
-            match bot.callbacks.get(callback_message.data.command.as_str()) {
-                Some(cb) => {
-                    (cb)(callback_message.message, callback_message.data.message_id).await?
+            let mut data = callback_message.data.clone();
+            if let Some(key) = bot.api.callback_key() {
+                if data.verify(key).is_err() {
+                    //TODO log
+                    bot.api
+                        .answer_callback_query(&callback_message.id, None)
+                        .await?;
+                    return Ok(None);
                 }
-                None => ResponseMessage {
-                    chat_id: chat_id.unwrap(),
-                    text: callback_message.data.command,
-                    parse_mode: None,
-                    reply_markup: None,
-                },
             }
-        }
-        Contents::Current(chat_id) if bot.enabled_current_command => {
-            let command_idx = bot.current_command.load(Ordering::Relaxed);
-            let current_command: &Command = bot.commands.get(command_idx).unwrap();
-            ResponseMessage {
-                chat_id,
-                text: current_command.name.clone().into_owned(),
-                parse_mode: None,
-                reply_markup: None,
+
+            if let Some(key) = data.stored_key() {
+                match bot.store().get(api::callback_store_key(key)).await? {
+                    Some(payload) => data.restore(String::from_utf8(payload)?),
+                    None => {
+                        bot.api
+                            .answer_callback_query(&callback_message.id, Some(EXPIRED_CALLBACK))
+                            .await?;
+                        return Ok(None);
+                    }
+                }
+            }
+
+            match bot
+                .callbacks
+                .get(data.command())
+                .or(bot.unknown_callback_handler.as_ref())
+            {
+                Some(cb) => cb(ctx.with_callback_data(data)).await?,
+                None => {
+                    bot.api
+                        .answer_callback_query(&callback_message.id, None)
+                        .await?;
+                    return Ok(None);
+                }
             }
         }
-        Contents::Current(_) => bail!("Command current is disabled"),
+        Contents::Current(chat_id) => current(&bot, *chat_id)?,
+        Contents::Command(command) if !bot.is_addressed_to_me(command.addressee.as_deref()) => {
+            return Ok(None)
+        }
+        Contents::Command(command) if command.command == "current" => {
+            current(&bot, command.chat_id)?
+        }
         Contents::Command(command) => {
-            let idx = bot
+            let ctx = ctx
+                .with_command(&command.command)
+                .with_args(command.args.clone());
+            let Some(idx) = bot
                 .commands
                 .iter()
                 .position(|existing| existing.name == command.command)
-                .ok_or_else(|| format_err!("Command with name: {} not found", command.command))?;
-            bot.current_command.store(idx, Ordering::Relaxed);
-
-            let text = format!("Command set to {}", command.command);
-            ResponseMessage {
-                chat_id: command.chat_id,
-                text,
-                parse_mode: None,
-                reply_markup: None,
+            else {
+                return match &bot.unknown_command_handler {
+                    Some(cb) => cb(ctx).await.map(Some),
+                    None => Ok(None),
+                };
+            };
+            if !bot
+                .is_allowed(bot.commands[idx].access.as_ref(), &update)
+                .await?
+            {
+                return bot.deny(&update).await;
+            }
+
+            if command.args.is_empty() && bot.enabled_current_command {
+                bot.current_command.store(idx, Ordering::Relaxed);
+
+                let text = format!("Command set to {}", command.command);
+                ResponseMessage {
+                    chat_id: command.chat_id,
+                    text,
+                    parse_mode: None,
+                    reply_markup: None,
+                }
+            } else {
+                (bot.commands[idx].cb)(ctx).await?
             }
         }
         Contents::Message(message) => {
-            if let Some(inline_command) = bot.inline_commands.get(message.text.as_str()) {
-                (inline_command.cb)(message).await?
+            if let Some(media) = message.media() {
+                match bot
+                    .media_handlers
+                    .route(&media)
+                    .or(bot.default_handler.as_ref())
+                {
+                    Some(cb) => cb(ctx).await?,
+                    None => return Ok(None),
+                }
+            } else if let Some(inline_command) = bot.inline_commands.get(message.text.as_str()) {
+                if !bot
+                    .is_allowed(inline_command.access.as_ref(), &update)
+                    .await?
+                {
+                    return bot.deny(&update).await;
+                }
+                (inline_command.cb)(ctx.with_command(&inline_command.name)).await?
+            } else if let Some((route, (captures, text))) = bot
+                .text_routes
+                .iter()
+                .find_map(|route| Some((route, route.pattern.matches(message)?)))
+            {
+                let ctx = ctx.with_captures(captures).with_args(Args::parse(text));
+                (route.cb)(ctx).await?
             } else {
-                let command_idx = bot.current_command.load(Ordering::Relaxed);
-                let current_command: &Command = bot.commands.get(command_idx).unwrap();
-                (current_command.cb)(message).await?
+                let ctx = ctx.with_args(Args::parse(&message.text));
+                match (bot.current_command(), &bot.default_handler) {
+                    (Some(current_command), _) => {
+                        if !bot
+                            .is_allowed(current_command.access.as_ref(), &update)
+                            .await?
+                        {
+                            return bot.deny(&update).await;
+                        }
+                        (current_command.cb)(ctx.with_command(&current_command.name)).await?
+                    }
+                    (None, Some(cb)) => cb(ctx).await?,
+                    (None, None) => return Ok(None),
+                }
             }
         }
+        Contents::ChannelPost(post) => match &bot.channel_post_handler {
+            Some(cb) => cb(ctx.with_args(Args::parse(&post.text))).await?,
+            None => return Ok(None),
+        },
+        Contents::EditedChannelPost(post) => match &bot.edited_channel_post_handler {
+            Some(cb) => cb(ctx.with_args(Args::parse(&post.text))).await?,
+            None => return Ok(None),
+        },
+        Contents::MyChatMember(_) => match &bot.my_chat_member_handler {
+            Some(cb) => return cb(ctx).await,
+            None => return Ok(None),
+        },
+        Contents::ChatMember(_) => match &bot.chat_member_handler {
+            Some(cb) => return cb(ctx).await,
+            None => return Ok(None),
+        },
+        Contents::ChatJoinRequest(request) => {
+            let Some(cb) = &bot.chat_join_request_handler else {
+                return Ok(None);
+            };
+            let (chat_id, user_id) = (request.chat.id, request.from.id);
+            match cb(ctx).await? {
+                JoinRequestDecision::Approve => {
+                    bot.api.approve_chat_join_request(chat_id, user_id).await?;
+                }
+                JoinRequestDecision::Decline => {
+                    bot.api.decline_chat_join_request(chat_id, user_id).await?;
+                }
+                JoinRequestDecision::Ignore => {}
+            }
+            return Ok(None);
+        }
+        Contents::InlineQuery(query) => {
+            let cb = bot
+                .inline_query_handler
+                .as_ref()
+                .ok_or_else(|| format_err!("Inline query handler is not set"))?;
+            let mut answer = cb(ctx).await?;
+            for result in &mut answer.results {
+                bot.api.seal(result.reply_markup_mut()).await?;
+            }
+            bot.api.answer_inline_query(&query.id, &answer).await?;
+            return Ok(None);
+        }
+        Contents::ChosenInlineResult(_) => {
+            if let Some(cb) = &bot.chosen_inline_result_handler {
+                cb(ctx).await?;
+            }
+            return Ok(None);
+        }
         Contents::None => bail!("Contents::NONE"),
     };
 
-    Ok(body)
+    Ok(Some(body))
+}
+
+const EXPIRED_CALLBACK: &str = "This button has expired";
+const NO_CURRENT_COMMAND: &str = "No command is set";
+/// Value of `Bot::current_command` until a command is selected.
+const NO_COMMAND: usize = usize::MAX;
+const CHAT_ADMIN_TTL: Duration = Duration::from_secs(5 * 60);
+
+fn update_key(update_id: u64) -> String {
+    format!("update:{}", update_id)
+}
+
+fn current(bot: &Bot, chat_id: ChatId) -> Fallible<ResponseMessage> {
+    if !bot.enabled_current_command {
+        bail!("Command current is disabled");
+    }
+
+    let text = match bot.current_command() {
+        Some(current_command) => current_command.name.clone().into_owned(),
+        None => NO_CURRENT_COMMAND.to_owned(),
+    };
+    Ok(ResponseMessage {
+        chat_id,
+        text,
+        parse_mode: None,
+        reply_markup: None,
+    })
 }
 
 struct Command {
     name: Cow<'static, str>,
     cb: CommandFn,
+    access: Option<AccessPolicy>,
+}
+
+struct MenuEntry {
+    command: BotCommand,
+    scope: BotCommandScope,
+    language_code: Option<String>,
+}
+
+#[derive(Default)]
+struct MediaHandlers {
+    photo: Option<CommandFn>,
+    document: Option<CommandFn>,
+    voice: Option<CommandFn>,
+    location: Option<CommandFn>,
+    contact: Option<CommandFn>,
+    sticker: Option<CommandFn>,
+    any: Option<CommandFn>,
+}
+
+impl MediaHandlers {
+    /// Picks the handler for the message payload, if one is registered.
+    fn route(&self, media: &Media) -> Option<&CommandFn> {
+        let specific = match media {
+            Media::Photo(_) => &self.photo,
+            Media::Document(_) => &self.document,
+            Media::Voice(_) => &self.voice,
+            Media::Location(_) => &self.location,
+            Media::Contact(_) => &self.contact,
+            Media::Sticker(_) => &self.sticker,
+        };
+        specific.as_ref().or(self.any.as_ref())
+    }
 }
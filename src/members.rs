@@ -0,0 +1,173 @@
+use crate::{Chat, ChatId, User};
+use serde::Deserialize;
+
+/// Change of a chat member status, sent as `my_chat_member` for the bot itself and as
+/// `chat_member` for other users.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberUpdated {
+    pub chat: Chat,
+    /// User who performed the change.
+    pub from: User,
+    pub date: i64,
+    pub old_chat_member: ChatMember,
+    pub new_chat_member: ChatMember,
+    pub invite_link: Option<ChatInviteLink>,
+    #[serde(default)]
+    pub via_join_request: bool,
+    #[serde(default)]
+    pub via_chat_folder_invite_link: bool,
+}
+
+impl ChatMemberUpdated {
+    /// The user was not in the chat before and is now.
+    pub fn joined(&self) -> bool {
+        !self.old_chat_member.is_member() && self.new_chat_member.is_member()
+    }
+
+    /// The user was in the chat before and is not anymore.
+    pub fn left(&self) -> bool {
+        self.old_chat_member.is_member() && !self.new_chat_member.is_member()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ChatMember {
+    #[serde(rename = "creator")]
+    Owner(ChatMemberOwner),
+    Administrator(ChatMemberAdministrator),
+    Member(ChatMemberMember),
+    Restricted(ChatMemberRestricted),
+    Left(ChatMemberLeft),
+    Kicked(ChatMemberBanned),
+}
+
+impl ChatMember {
+    pub fn user(&self) -> &User {
+        match self {
+            ChatMember::Owner(member) => &member.user,
+            ChatMember::Administrator(member) => &member.user,
+            ChatMember::Member(member) => &member.user,
+            ChatMember::Restricted(member) => &member.user,
+            ChatMember::Left(member) => &member.user,
+            ChatMember::Kicked(member) => &member.user,
+        }
+    }
+
+    pub fn is_member(&self) -> bool {
+        match self {
+            ChatMember::Owner(_) | ChatMember::Administrator(_) | ChatMember::Member(_) => true,
+            ChatMember::Restricted(member) => member.is_member,
+            ChatMember::Left(_) | ChatMember::Kicked(_) => false,
+        }
+    }
+
+    pub fn is_administrator(&self) -> bool {
+        matches!(self, ChatMember::Owner(_) | ChatMember::Administrator(_))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberOwner {
+    pub user: User,
+    pub is_anonymous: bool,
+    pub custom_title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberAdministrator {
+    pub user: User,
+    #[serde(default)]
+    pub can_be_edited: bool,
+    #[serde(default)]
+    pub is_anonymous: bool,
+    #[serde(default)]
+    pub can_manage_chat: bool,
+    #[serde(default)]
+    pub can_delete_messages: bool,
+    #[serde(default)]
+    pub can_manage_video_chats: bool,
+    #[serde(default)]
+    pub can_restrict_members: bool,
+    #[serde(default)]
+    pub can_promote_members: bool,
+    #[serde(default)]
+    pub can_change_info: bool,
+    #[serde(default)]
+    pub can_invite_users: bool,
+    #[serde(default)]
+    pub can_post_messages: bool,
+    #[serde(default)]
+    pub can_edit_messages: bool,
+    #[serde(default)]
+    pub can_pin_messages: bool,
+    pub custom_title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberMember {
+    pub user: User,
+    pub until_date: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberRestricted {
+    pub user: User,
+    pub is_member: bool,
+    /// Unix time when restrictions will be lifted, 0 for forever.
+    pub until_date: i64,
+    #[serde(default)]
+    pub can_send_messages: bool,
+    #[serde(default)]
+    pub can_send_other_messages: bool,
+    #[serde(default)]
+    pub can_invite_users: bool,
+    #[serde(default)]
+    pub can_pin_messages: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberLeft {
+    pub user: User,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMemberBanned {
+    pub user: User,
+    /// Unix time when the ban will be lifted, 0 for forever.
+    pub until_date: i64,
+}
+
+/// Request to join a chat, sent when the bot can invite users to it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatJoinRequest {
+    pub chat: Chat,
+    pub from: User,
+    /// Private chat with the user, usable for 5 minutes or until the request is processed.
+    pub user_chat_id: ChatId,
+    pub date: i64,
+    pub bio: Option<String>,
+    pub invite_link: Option<ChatInviteLink>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatInviteLink {
+    pub invite_link: String,
+    pub creator: User,
+    pub creates_join_request: bool,
+    pub is_primary: bool,
+    pub is_revoked: bool,
+    pub name: Option<String>,
+    pub expire_date: Option<i64>,
+    pub member_limit: Option<u32>,
+    pub pending_join_request_count: Option<u32>,
+}
+
+/// What to do with a [`ChatJoinRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinRequestDecision {
+    Approve,
+    Decline,
+    /// Leave the request pending, e.g. for a human administrator.
+    Ignore,
+}
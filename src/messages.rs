@@ -1,25 +1,64 @@
+use crate::{Args, ChatJoinRequest, ChatMemberUpdated, ChosenInlineResult, Fallible, InlineQuery};
+use anyhow::{bail, format_err};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
 use serde::{
     de,
-    de::{MapAccess, Visitor},
+    de::{DeserializeOwned, MapAccess, Visitor},
     Deserialize, Deserializer, Serialize, Serializer,
 };
 use serde_json::Value;
+use sha2::Sha256;
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Update {
     pub update_id: u64,
     pub contents: Contents,
 }
 
 impl Update {
-    pub fn chat_id(&self) -> Option<u64> {
+    pub fn chat_id(&self) -> Option<ChatId> {
         match &self.contents {
             Contents::Command(command) => Some(command.chat_id),
-            Contents::Message(message) => Some(message.chat.id),
-            Contents::CallbackMessage(callback_message) => Some(callback_message.message.chat.id),
+            Contents::Message(message)
+            | Contents::ChannelPost(message)
+            | Contents::EditedChannelPost(message) => Some(message.chat.id),
+            Contents::CallbackMessage(callback_message) => callback_message
+                .message
+                .as_ref()
+                .map(|message| message.chat.id),
+            Contents::MyChatMember(updated) | Contents::ChatMember(updated) => {
+                Some(updated.chat.id)
+            }
+            Contents::ChatJoinRequest(request) => Some(request.chat.id),
             Contents::Current(chat_id) => Some(*chat_id),
-            Contents::None => None,
+            Contents::InlineQuery(_) | Contents::ChosenInlineResult(_) | Contents::None => None,
+        }
+    }
+
+    /// Message the update is about, including the message of a pressed button unless it was
+    /// sent in inline mode.
+    pub fn message(&self) -> Option<&Message> {
+        match &self.contents {
+            Contents::Message(message)
+            | Contents::ChannelPost(message)
+            | Contents::EditedChannelPost(message) => Some(message),
+            Contents::Command(command) => Some(&command.message),
+            Contents::CallbackMessage(callback_message) => callback_message.message.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// User who caused the update.
+    pub fn user(&self) -> Option<&User> {
+        match &self.contents {
+            Contents::CallbackMessage(callback_message) => Some(&callback_message.from),
+            Contents::InlineQuery(query) => Some(&query.from),
+            Contents::ChosenInlineResult(result) => Some(&result.from),
+            Contents::MyChatMember(updated) | Contents::ChatMember(updated) => Some(&updated.from),
+            Contents::ChatJoinRequest(request) => Some(&request.from),
+            _ => self.message().and_then(|message| message.from.as_ref()),
         }
     }
 }
@@ -72,22 +111,109 @@ impl<'de> Deserialize<'de> for Update {
                             if let Some(text) = text {
                                 contents = match text.chars().next() {
                                     Some('/') => {
-                                        let command = String::from(&text[1..]);
+                                        let (command, args) = text[1..]
+                                            .split_once(char::is_whitespace)
+                                            .unwrap_or((&text[1..], ""));
+                                        let (command, addressee) = match command.split_once('@') {
+                                            Some((command, addressee)) => {
+                                                (command.to_owned(), Some(addressee.to_owned()))
+                                            }
+                                            None => (command.to_owned(), None),
+                                        };
+                                        let args = Args::parse(args);
                                         let chat_id =
-                                            value["chat"]["id"].as_u64().ok_or_else(|| {
-                                                de::Error::custom("Can not parse chat id")
-                                            })?;
-                                        if command == "current" {
+                                            value["chat"]["id"].as_i64().map(ChatId).ok_or_else(
+                                                || de::Error::custom("Can not parse chat id"),
+                                            )?;
+                                        if command == "current" && addressee.is_none() {
                                             Some(Contents::Current(chat_id))
                                         } else {
-                                            Some(Contents::Command(Command { command, chat_id }))
+                                            let message = Message::deserialize(&value)
+                                                .map_err(de::Error::custom)?;
+                                            Some(Contents::Command(Command {
+                                                command,
+                                                addressee,
+                                                args,
+                                                chat_id,
+                                                message,
+                                            }))
                                         }
                                     }
                                     _ => Some(Contents::Message(
                                         Message::deserialize(value).map_err(de::Error::custom)?,
                                     )),
                                 }
+                            } else {
+                                let message =
+                                    Message::deserialize(value).map_err(de::Error::custom)?;
+                                if message.media().is_some() {
+                                    contents = Some(Contents::Message(message));
+                                }
+                            }
+                        }
+                        "channel_post" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
+                            }
+
+                            contents = Some(Contents::ChannelPost(
+                                Message::deserialize(value).map_err(de::Error::custom)?,
+                            ));
+                        }
+                        "edited_channel_post" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
+                            }
+
+                            contents = Some(Contents::EditedChannelPost(
+                                Message::deserialize(value).map_err(de::Error::custom)?,
+                            ));
+                        }
+                        "my_chat_member" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
+                            }
+
+                            contents = Some(Contents::MyChatMember(
+                                ChatMemberUpdated::deserialize(value).map_err(de::Error::custom)?,
+                            ));
+                        }
+                        "chat_member" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
+                            }
+
+                            contents = Some(Contents::ChatMember(
+                                ChatMemberUpdated::deserialize(value).map_err(de::Error::custom)?,
+                            ));
+                        }
+                        "chat_join_request" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
+                            }
+
+                            contents = Some(Contents::ChatJoinRequest(
+                                ChatJoinRequest::deserialize(value).map_err(de::Error::custom)?,
+                            ));
+                        }
+                        "inline_query" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
+                            }
+
+                            contents = Some(Contents::InlineQuery(
+                                InlineQuery::deserialize(value).map_err(de::Error::custom)?,
+                            ));
+                        }
+                        "chosen_inline_result" => {
+                            if contents.is_some() {
+                                return Err(de::Error::duplicate_field("contents"));
                             }
+
+                            contents = Some(Contents::ChosenInlineResult(
+                                ChosenInlineResult::deserialize(value)
+                                    .map_err(de::Error::custom)?,
+                            ));
                         }
                         "edited_message" => {
                             if contents.is_some() {
@@ -117,38 +243,296 @@ impl<'de> Deserialize<'de> for Update {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Message {
     pub message_id: u64,
+    #[serde(default)]
     pub text: String,
-    pub from: User,
+    /// Empty for messages sent to channels.
+    pub from: Option<User>,
+    /// Chat on whose behalf the message was sent, e.g. the channel for channel posts.
+    pub sender_chat: Option<Chat>,
     pub chat: Chat,
+    pub caption: Option<String>,
+    pub photo: Option<Vec<PhotoSize>>,
+    pub document: Option<Document>,
+    pub voice: Option<Voice>,
+    pub location: Option<Location>,
+    pub contact: Option<Contact>,
+    pub sticker: Option<Sticker>,
+}
+
+impl Message {
+    /// Returns the non-text payload of the message, if any.
+    pub fn media(&self) -> Option<Media> {
+        if let Some(photo) = &self.photo {
+            Some(Media::Photo(photo.clone()))
+        } else if let Some(document) = &self.document {
+            Some(Media::Document(document.clone()))
+        } else if let Some(voice) = &self.voice {
+            Some(Media::Voice(voice.clone()))
+        } else if let Some(location) = &self.location {
+            Some(Media::Location(location.clone()))
+        } else if let Some(contact) = &self.contact {
+            Some(Media::Contact(contact.clone()))
+        } else {
+            self.sticker.clone().map(Media::Sticker)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Media {
+    /// All available sizes of the photo, smallest first.
+    Photo(Vec<PhotoSize>),
+    Document(Document),
+    Voice(Voice),
+    Location(Location),
+    Contact(Contact),
+    Sticker(Sticker),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug, Clone, Deserialize)]
+pub struct PhotoSize {
+    pub file_id: String,
+    pub file_unique_id: String,
+    pub width: u32,
+    pub height: u32,
+    pub file_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Document {
+    pub file_id: String,
+    pub file_unique_id: String,
+    pub thumbnail: Option<PhotoSize>,
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub file_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Voice {
+    pub file_id: String,
+    pub file_unique_id: String,
+    pub duration: u32,
+    pub mime_type: Option<String>,
+    pub file_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Location {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub horizontal_accuracy: Option<f64>,
+    pub live_period: Option<u32>,
+    pub heading: Option<u32>,
+    pub proximity_alert_radius: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Contact {
+    pub phone_number: String,
+    pub first_name: String,
+    pub last_name: Option<String>,
+    pub user_id: Option<UserId>,
+    pub vcard: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sticker {
+    pub file_id: String,
+    pub file_unique_id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub width: u32,
+    pub height: u32,
+    pub is_animated: bool,
+    pub is_video: bool,
+    pub emoji: Option<String>,
+    pub set_name: Option<String>,
+}
+
+/// File ready to be downloaded, as returned by `getFile`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct File {
+    pub file_id: String,
+    pub file_unique_id: String,
+    pub file_size: Option<u64>,
+    /// Path for `Api::download_file`; an absolute path on the server in local mode.
+    pub file_path: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct CallbackMessage {
     pub id: String,
     pub from: User,
-    pub message: Message,
+    /// Message with the button; `None` for messages sent in inline mode.
+    pub message: Option<Message>,
+    /// Identifier of the message with the button when it was sent in inline mode.
+    pub inline_message_id: Option<String>,
     pub data: CallbackData,
 }
 
-#[derive(Debug, Clone)]
+/// `callback_data` of an inline keyboard button: a command name and a typed payload.
+///
+/// The payload is encoded with postcard and base64url, and the whole value is checked against
+/// Telegram's limit of [`CallbackData::MAX_LEN`] bytes when it is built. When the bot signs
+/// callbacks, [`CallbackData::SIGNATURE_LEN`] bytes of the limit are taken by the signature, and
+/// one more by the `/` of data without a payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CallbackData {
-    pub command: String,
-    pub message_id: Option<u64>,
+    command: String,
+    payload: String,
+    signature: Option<String>,
 }
 
+impl CallbackData {
+    pub const MAX_LEN: usize = 64;
+    /// Encoded length of the signature including its separator.
+    pub const SIGNATURE_LEN: usize = 1 + 11;
+    const SEPARATOR: char = '/';
+    const SIGNATURE_SEPARATOR: char = '.';
+    /// Marks a payload that is a key in the bot store; not part of the base64url alphabet.
+    const STORED_PREFIX: char = '~';
+    /// Bytes of the HMAC kept in the signature.
+    const MAC_LEN: usize = 8;
+
+    /// Callback data without a payload; the handler receives `()`.
+    pub fn new<C: Into<String>>(command: C) -> Fallible<Self> {
+        Self::with_payload(command, &())
+    }
+
+    pub fn with_payload<C: Into<String>, T: Serialize>(command: C, payload: &T) -> Fallible<Self> {
+        let data = Self::with_large_payload(command, payload)?;
+        data.check_len()?;
+        Ok(data)
+    }
+
+    /// Callback data whose payload may exceed the limit.
+    ///
+    /// Oversized payloads are kept in the bot store when the keyboard is sent, see
+    /// `Bot::store_large_callbacks`; otherwise sending the keyboard fails.
+    pub fn with_large_payload<C: Into<String>, T: Serialize>(
+        command: C,
+        payload: &T,
+    ) -> Fallible<Self> {
+        let command = command.into();
+        if command.contains(Self::SEPARATOR) {
+            bail!(
+                "Callback command `{}` must not contain `{}`",
+                command,
+                Self::SEPARATOR
+            );
+        }
+
+        Ok(Self {
+            command,
+            payload: URL_SAFE_NO_PAD.encode(postcard::to_stdvec(payload)?),
+            signature: None,
+        })
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn payload<T: DeserializeOwned>(&self) -> Fallible<T> {
+        let bytes = URL_SAFE_NO_PAD.decode(&self.payload)?;
+        Ok(postcard::from_bytes(&bytes)?)
+    }
+
+    pub(crate) fn check_len(&self) -> Fallible<()> {
+        let len = self.encode().len();
+        if len > Self::MAX_LEN {
+            bail!(
+                "Callback data for `{}` is {} bytes, at most {} are allowed",
+                self.command,
+                len,
+                Self::MAX_LEN
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the data does not fit the limit, leaving room for a signature if `signed`.
+    pub(crate) fn is_oversized(&self, signed: bool) -> bool {
+        let len = match (&self.signature, signed) {
+            // Signed data keeps the `/` even when the payload is empty.
+            (None, true) => self.signed_part().len() + Self::SIGNATURE_LEN,
+            _ => self.encode().len(),
+        };
+        len > Self::MAX_LEN
+    }
+
+    /// Key of the payload kept in the bot store, if it was too large to send.
+    pub(crate) fn stored_key(&self) -> Option<&str> {
+        self.payload.strip_prefix(Self::STORED_PREFIX)
+    }
+
+    /// Replaces the payload with a reference to `key`, returning the payload.
+    pub(crate) fn store_as(&mut self, key: &str) -> String {
+        std::mem::replace(&mut self.payload, format!("{}{}", Self::STORED_PREFIX, key))
+    }
+
+    /// Puts back a payload fetched from the bot store.
+    pub(crate) fn restore(&mut self, payload: String) {
+        self.payload = payload;
+    }
+
+    /// Appends a truncated HMAC of the command and payload.
+    pub(crate) fn sign(&mut self, key: &CallbackKey) -> Fallible<()> {
+        let mut mac = key.clone();
+        mac.update(self.signed_part().as_bytes());
+        let tag = mac.finalize().into_bytes();
+        self.signature = Some(URL_SAFE_NO_PAD.encode(&tag[..Self::MAC_LEN]));
+        self.check_len()
+    }
+
+    pub(crate) fn verify(&self, key: &CallbackKey) -> Fallible<()> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| format_err!("Callback data for `{}` is not signed", self.command))?;
+        let tag = URL_SAFE_NO_PAD.decode(signature)?;
+        // A shorter tag would be accepted by `verify_truncated_left` and is easy to guess.
+        if tag.len() != Self::MAC_LEN {
+            bail!("Invalid signature of callback `{}`", self.command);
+        }
+
+        let mut mac = key.clone();
+        mac.update(self.signed_part().as_bytes());
+        mac.verify_truncated_left(&tag)
+            .map_err(|_| format_err!("Invalid signature of callback `{}`", self.command))
+    }
+
+    fn signed_part(&self) -> String {
+        format!("{}{}{}", self.command, Self::SEPARATOR, self.payload)
+    }
+
+    fn encode(&self) -> String {
+        match (&self.signature, self.payload.is_empty()) {
+            (Some(signature), _) => format!(
+                "{}{}{}",
+                self.signed_part(),
+                Self::SIGNATURE_SEPARATOR,
+                signature
+            ),
+            (None, true) => self.command.clone(),
+            (None, false) => self.signed_part(),
+        }
+    }
+}
+
+/// Secret key used to sign callback data.
+pub(crate) type CallbackKey = Hmac<Sha256>;
+
 impl Serialize for CallbackData {
     fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
     where
         S: Serializer,
     {
-        if let Some(message_id) = self.message_id {
-            serializer.serialize_str(&format!("{}/{}", self.command, message_id))
-        } else {
-            serializer.serialize_str(&self.command)
-        }
+        serializer.serialize_str(&self.encode())
     }
 }
 
@@ -167,20 +551,19 @@ impl<'de> Deserialize<'de> for CallbackData {
             }
 
             fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
-                let mut iter = value.split('/');
-                let command = iter
-                    .next()
-                    .ok_or_else(|| de::Error::custom("command not found"))?
-                    .to_owned();
-                let message_id = iter
-                    .next()
-                    .map(|message_id| message_id.parse())
-                    .transpose()
-                    .map_err(de::Error::custom)?;
+                let (command, rest) = value
+                    .split_once(CallbackData::SEPARATOR)
+                    .unwrap_or((value, ""));
+                let (payload, signature) = match rest.split_once(CallbackData::SIGNATURE_SEPARATOR)
+                {
+                    Some((payload, signature)) => (payload, Some(signature.to_owned())),
+                    None => (rest, None),
+                };
 
                 Ok(CallbackData {
-                    command,
-                    message_id,
+                    command: command.to_owned(),
+                    payload: payload.to_owned(),
+                    signature,
                 })
             }
         }
@@ -189,35 +572,177 @@ impl<'de> Deserialize<'de> for CallbackData {
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// Identifier of a chat; negative for groups, supergroups and channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ChatId(pub i64);
+
+impl fmt::Display for ChatId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i64> for ChatId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<UserId> for ChatId {
+    /// A private chat has the same id as the user in it.
+    fn from(id: UserId) -> Self {
+        Self(id.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserId(pub i64);
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i64> for UserId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct User {
-    pub id: u64,
+    pub id: UserId,
+    pub is_bot: bool,
+    pub first_name: String,
+    pub last_name: Option<String>,
+    pub username: Option<String>,
+    /// IETF language tag of the user's client, e.g. `en` or `pt-br`.
+    pub language_code: Option<String>,
+    #[serde(default)]
+    pub is_premium: bool,
+    /// Only returned by `getMe`.
+    pub can_join_groups: Option<bool>,
+    /// Only returned by `getMe`.
+    pub can_read_all_group_messages: Option<bool>,
+    /// Only returned by `getMe`.
+    pub supports_inline_queries: Option<bool>,
+}
+
+impl User {
+    /// First and last name separated by a space.
+    pub fn full_name(&self) -> String {
+        match &self.last_name {
+            Some(last_name) => format!("{} {}", self.first_name, last_name),
+            None => self.first_name.clone(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Chat {
-    pub id: u64,
+    pub id: ChatId,
+    #[serde(rename = "type")]
+    pub kind: ChatKind,
+    pub title: Option<String>,
+    pub username: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    #[serde(default)]
+    pub is_forum: bool,
 }
 
-#[derive(Debug)]
+/// Full information about a chat, as returned by `getChat`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatFullInfo {
+    pub id: ChatId,
+    #[serde(rename = "type")]
+    pub kind: ChatKind,
+    pub title: Option<String>,
+    pub username: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    #[serde(default)]
+    pub is_forum: bool,
+    pub photo: Option<ChatPhoto>,
+    #[serde(default)]
+    pub active_usernames: Vec<String>,
+    pub bio: Option<String>,
+    pub description: Option<String>,
+    pub invite_link: Option<String>,
+    pub pinned_message: Option<Box<Message>>,
+    pub slow_mode_delay: Option<u32>,
+    pub message_auto_delete_time: Option<u32>,
+    #[serde(default)]
+    pub has_protected_content: bool,
+    #[serde(default)]
+    pub has_private_forwards: bool,
+    #[serde(default)]
+    pub join_to_send_messages: bool,
+    #[serde(default)]
+    pub join_by_request: bool,
+    pub sticker_set_name: Option<String>,
+    pub linked_chat_id: Option<ChatId>,
+    pub location: Option<ChatLocation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatPhoto {
+    pub small_file_id: String,
+    pub small_file_unique_id: String,
+    pub big_file_id: String,
+    pub big_file_unique_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatLocation {
+    pub location: Location,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatKind {
+    Private,
+    Group,
+    Supergroup,
+    Channel,
+}
+
+#[derive(Debug, Clone)]
 pub struct Command {
     pub command: String,
-    pub chat_id: u64,
+    /// Bot username from `/command@username`, as sent in group chats.
+    pub addressee: Option<String>,
+    pub args: Args,
+    pub chat_id: ChatId,
+    pub message: Message,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Contents {
     Command(Command),
     Message(Message),
     CallbackMessage(CallbackMessage),
-    Current(u64),
+    ChannelPost(Message),
+    EditedChannelPost(Message),
+    /// The bot's own membership in a chat changed.
+    MyChatMember(ChatMemberUpdated),
+    /// Membership of another user changed; must be requested in `allowed_updates`.
+    ChatMember(ChatMemberUpdated),
+    ChatJoinRequest(ChatJoinRequest),
+    Current(ChatId),
+    InlineQuery(InlineQuery),
+    ChosenInlineResult(ChosenInlineResult),
     None,
 }
 
 #[derive(Serialize, Clone)]
 #[serde(rename = "message")]
 pub struct ResponseMessage {
-    pub chat_id: u64,
+    pub chat_id: ChatId,
     pub text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parse_mode: Option<String>,
@@ -225,13 +750,206 @@ pub struct ResponseMessage {
     pub reply_markup: Option<InlineKeyboardMarkup>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct InlineKeyboardMarkup {
     pub inline_keyboard: [Vec<InlineKeyboardButton>; 1],
 }
 
-#[derive(Serialize, Clone)]
+impl InlineKeyboardMarkup {
+    pub(crate) fn callback_data_mut(&mut self) -> impl Iterator<Item = &mut CallbackData> {
+        self.inline_keyboard
+            .iter_mut()
+            .flatten()
+            .map(|button| &mut button.callback_data)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
 pub struct InlineKeyboardButton {
     pub text: String,
     pub callback_data: CallbackData,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotCommand {
+    pub command: String,
+    pub description: String,
+}
+
+/// Set of users and chats a command list applies to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotCommandScope {
+    #[default]
+    Default,
+    AllPrivateChats,
+    AllGroupChats,
+    AllChatAdministrators,
+    Chat {
+        chat_id: ChatId,
+    },
+    ChatAdministrators {
+        chat_id: ChatId,
+    },
+    ChatMember {
+        chat_id: ChatId,
+        user_id: UserId,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallbackData, CallbackKey};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use hmac::Mac;
+
+    fn encode(data: &CallbackData) -> String {
+        serde_json::to_value(data)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    fn decode(value: &str) -> CallbackData {
+        serde_json::from_value(value.into()).unwrap()
+    }
+
+    #[test]
+    fn empty_payload_encodes_to_the_command() {
+        let data = CallbackData::new("menu").unwrap();
+        assert_eq!(encode(&data), "menu");
+        assert_eq!(decode("menu"), data);
+        data.payload::<()>().unwrap();
+    }
+
+    #[test]
+    fn payload_round_trips() {
+        let data = CallbackData::with_payload("buy", &(42u32, "BTC".to_owned())).unwrap();
+        let decoded = decode(&encode(&data));
+        assert_eq!(decoded, data);
+        assert_eq!(decoded.command(), "buy");
+        assert_eq!(
+            decoded.payload::<(u32, String)>().unwrap(),
+            (42, "BTC".to_owned())
+        );
+    }
+
+    #[test]
+    fn command_may_contain_a_dot() {
+        let data = CallbackData::new("v1.menu").unwrap();
+        assert_eq!(decode(&encode(&data)), data);
+
+        let data = CallbackData::with_payload("v1.buy", &7u8).unwrap();
+        let decoded = decode(&encode(&data));
+        assert_eq!(decoded.command(), "v1.buy");
+        assert_eq!(decoded.payload::<u8>().unwrap(), 7);
+    }
+
+    #[test]
+    fn command_must_not_contain_the_separator() {
+        assert!(CallbackData::new("a/b").is_err());
+    }
+
+    #[test]
+    fn decodes_command_payload_and_signature() {
+        let data = decode("cmd/payload.sig");
+        assert_eq!(data.command, "cmd");
+        assert_eq!(data.payload, "payload");
+        assert_eq!(data.signature.as_deref(), Some("sig"));
+        assert_eq!(encode(&data), "cmd/payload.sig");
+    }
+
+    #[test]
+    fn limits_the_encoded_length() {
+        let longest = "c".repeat(CallbackData::MAX_LEN);
+        assert!(CallbackData::new(longest.as_str()).is_ok());
+        assert!(CallbackData::new(format!("{}c", longest)).is_err());
+        assert!(CallbackData::with_large_payload(format!("{}c", longest), &()).is_ok());
+    }
+
+    #[test]
+    fn reserves_room_for_the_signature() {
+        // `/` and the payload `Bw` of `7u8` follow the command.
+        let command = "c".repeat(CallbackData::MAX_LEN - CallbackData::SIGNATURE_LEN - 3);
+        let data = CallbackData::with_payload(command.as_str(), &7u8).unwrap();
+        assert!(!data.is_oversized(true));
+
+        let data = CallbackData::with_payload(format!("{}c", command), &7u8).unwrap();
+        assert!(data.is_oversized(true));
+        assert!(!data.is_oversized(false));
+    }
+
+    fn key() -> CallbackKey {
+        CallbackKey::new_from_slice(b"secret").unwrap()
+    }
+
+    fn signed(command: &str) -> CallbackData {
+        let mut data = CallbackData::with_payload(command, &7u8).unwrap();
+        data.sign(&key()).unwrap();
+        data
+    }
+
+    #[test]
+    fn signing_keeps_the_separator_of_an_empty_payload() {
+        let command = "c".repeat(CallbackData::MAX_LEN - CallbackData::SIGNATURE_LEN);
+        let mut data = CallbackData::new(command.as_str()).unwrap();
+        assert!(data.is_oversized(true));
+        assert!(data.sign(&key()).is_err());
+
+        let mut data = CallbackData::new(&command[1..]).unwrap();
+        assert!(!data.is_oversized(true));
+        data.sign(&key()).unwrap();
+        assert_eq!(encode(&data).len(), CallbackData::MAX_LEN);
+        assert_eq!(decode(&encode(&data)), data);
+    }
+
+    #[test]
+    fn signed_data_verifies() {
+        let data = decode(&encode(&signed("admin")));
+        data.verify(&key()).unwrap();
+        assert_eq!(data.payload::<u8>().unwrap(), 7);
+    }
+
+    #[test]
+    fn rejects_unsigned_data() {
+        let data = CallbackData::with_payload("admin", &7u8).unwrap();
+        assert!(data.verify(&key()).is_err());
+    }
+
+    #[test]
+    fn rejects_forged_data() {
+        let signature = signed("admin").signature.unwrap();
+        let forged = decode(&format!("admin/CA.{}", signature));
+        assert!(forged.verify(&key()).is_err());
+
+        let other_key = CallbackKey::new_from_slice(b"other").unwrap();
+        assert!(signed("admin").verify(&other_key).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_signatures() {
+        let data = signed("admin");
+        let tag = URL_SAFE_NO_PAD
+            .decode(data.signature.as_ref().unwrap())
+            .unwrap();
+        for len in 0..CallbackData::MAC_LEN {
+            let truncated = CallbackData {
+                signature: Some(URL_SAFE_NO_PAD.encode(&tag[..len])),
+                ..data.clone()
+            };
+            assert!(truncated.verify(&key()).is_err());
+        }
+
+        // Every 1-byte tag, as an attacker guessing one would try.
+        let payload = &data.payload;
+        for byte in 0..=u8::MAX {
+            let guess = decode(&format!(
+                "admin/{}.{}",
+                payload,
+                URL_SAFE_NO_PAD.encode([byte])
+            ));
+            assert!(guess.verify(&key()).is_err());
+        }
+    }
+}
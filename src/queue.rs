@@ -0,0 +1,193 @@
+use crate::ChatId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedMutexGuard, OwnedSemaphorePermit, Semaphore};
+
+/// Limits on handling updates concurrently, see `Bot::set_concurrency`.
+///
+/// Updates of one chat are always handled one at a time, in the order they arrived.
+#[derive(Debug, Clone, Copy)]
+pub struct Concurrency {
+    /// Handlers running at once across all chats, at least 1; `None` for no limit.
+    pub max_handlers: Option<usize>,
+    /// Updates of one chat waiting or being handled before new ones of the chat are refused,
+    /// at least 1.
+    pub max_queued_per_chat: usize,
+    /// Updates waiting or being handled overall before new ones are refused, at least 1.
+    pub max_queued: usize,
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Self {
+            max_handlers: Some(64),
+            max_queued_per_chat: 16,
+            max_queued: 1024,
+        }
+    }
+}
+
+/// Queues of updates per chat, limited as set by [`Concurrency`].
+pub(crate) struct ChatQueues {
+    limits: Concurrency,
+    handlers: Option<Arc<Semaphore>>,
+    queued: Mutex<Queued>,
+}
+
+#[derive(Default)]
+struct Queued {
+    total: usize,
+    chats: HashMap<ChatId, ChatQueue>,
+}
+
+struct ChatQueue {
+    len: usize,
+    turn: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Permission to handle an update; the next update of the chat waits until it is dropped.
+pub(crate) struct Turn<'a> {
+    queues: &'a ChatQueues,
+    chat_id: Option<ChatId>,
+    _chat: Option<OwnedMutexGuard<()>>,
+    _handler: Option<OwnedSemaphorePermit>,
+}
+
+impl ChatQueues {
+    pub(crate) fn new(limits: Concurrency) -> Self {
+        Self {
+            limits,
+            handlers: limits
+                .max_handlers
+                .map(|max_handlers| Arc::new(Semaphore::new(max_handlers.max(1)))),
+            queued: Mutex::default(),
+        }
+    }
+
+    /// Waits for the turn of an update of `chat_id`, or returns `None` right away when too
+    /// many updates are waiting.
+    pub(crate) async fn enter(&self, chat_id: Option<ChatId>) -> Option<Turn<'_>> {
+        let chat_turn = {
+            let mut queued = self.queued.lock().unwrap();
+            if queued.total >= self.limits.max_queued.max(1) {
+                return None;
+            }
+
+            let chat_turn = match chat_id {
+                Some(chat_id) => {
+                    let queue = queued.chats.entry(chat_id).or_insert_with(|| ChatQueue {
+                        len: 0,
+                        turn: Arc::default(),
+                    });
+                    if queue.len >= self.limits.max_queued_per_chat.max(1) {
+                        return None;
+                    }
+                    queue.len += 1;
+                    Some(queue.turn.clone())
+                }
+                None => None,
+            };
+            queued.total += 1;
+            chat_turn
+        };
+
+        // Leaves the queue even if the update is dropped while waiting.
+        let mut turn = Turn {
+            queues: self,
+            chat_id,
+            _chat: None,
+            _handler: None,
+        };
+        if let Some(chat_turn) = chat_turn {
+            turn._chat = Some(chat_turn.lock_owned().await);
+        }
+        if let Some(handlers) = &self.handlers {
+            turn._handler = handlers.clone().acquire_owned().await.ok();
+        }
+        Some(turn)
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let mut queued = self.queues.queued.lock().unwrap();
+        queued.total -= 1;
+        if let Some(chat_id) = self.chat_id {
+            if let Some(queue) = queued.chats.get_mut(&chat_id) {
+                queue.len -= 1;
+                if queue.len == 0 {
+                    queued.chats.remove(&chat_id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChatQueues, Concurrency};
+    use crate::ChatId;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn runs_handlers_when_limited_to_zero() {
+        let queues = ChatQueues::new(Concurrency {
+            max_handlers: Some(0),
+            max_queued_per_chat: 0,
+            max_queued: 0,
+        });
+        let turn = tokio::time::timeout(Duration::from_secs(1), queues.enter(Some(ChatId(1))));
+        assert!(turn.await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn handles_updates_of_a_chat_one_at_a_time_in_order() {
+        let queues = Arc::new(ChatQueues::new(Concurrency::default()));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = queues.enter(Some(ChatId(1))).await.unwrap();
+
+        let mut tasks = Vec::new();
+        for update in 0..3 {
+            let (queues, log) = (queues.clone(), log.clone());
+            tasks.push(tokio::spawn(async move {
+                let _turn = queues.enter(Some(ChatId(1))).await.unwrap();
+                log.lock().unwrap().push(format!("start {}", update));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                log.lock().unwrap().push(format!("end {}", update));
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(log.lock().unwrap().is_empty());
+
+        drop(first);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let log = log.lock().unwrap();
+        let expected = ["start 0", "end 0", "start 1", "end 1", "start 2", "end 2"];
+        assert_eq!(*log, expected);
+    }
+
+    #[tokio::test]
+    async fn handles_updates_of_different_chats_at_once() {
+        let queues = ChatQueues::new(Concurrency::default());
+        let _first = queues.enter(Some(ChatId(1))).await.unwrap();
+
+        let second = tokio::time::timeout(Duration::from_secs(1), queues.enter(Some(ChatId(2))));
+        assert!(second.await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn refuses_updates_of_a_full_chat_queue() {
+        let queues = ChatQueues::new(Concurrency {
+            max_queued_per_chat: 1,
+            ..Concurrency::default()
+        });
+        let _first = queues.enter(Some(ChatId(1))).await.unwrap();
+
+        let refused = tokio::time::timeout(Duration::from_secs(1), queues.enter(Some(ChatId(1))));
+        assert!(refused.await.unwrap().is_none());
+        assert!(queues.enter(Some(ChatId(2))).await.is_some());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::handler::{Context, FromUpdate, HandlerFn};
+use crate::{Fallible, Message, ResponseMessage};
+use anyhow::format_err;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// How a text route matches incoming messages, see `Bot::add_text_route`.
+pub enum TextPattern {
+    /// Text matches the regex anywhere; use `^...$` to match the whole text.
+    Regex(Regex),
+    /// Text starts with the prefix; the rest of the text is parsed as [`Args`](crate::Args).
+    Prefix(String),
+    /// Text is equal to the string, ignoring case.
+    IgnoreCase(String),
+    Predicate(Box<dyn Fn(&Message) -> bool + Send + Sync + 'static>),
+}
+
+impl TextPattern {
+    pub fn regex(pattern: &str) -> Fallible<Self> {
+        Ok(TextPattern::Regex(Regex::new(pattern)?))
+    }
+
+    pub fn prefix<S: Into<String>>(prefix: S) -> Self {
+        TextPattern::Prefix(prefix.into())
+    }
+
+    pub fn ignore_case(text: &str) -> Self {
+        TextPattern::IgnoreCase(text.to_lowercase())
+    }
+
+    pub fn predicate<F>(predicate: F) -> Self
+    where
+        F: Fn(&Message) -> bool + Send + Sync + 'static,
+    {
+        TextPattern::Predicate(Box::new(predicate))
+    }
+
+    /// Returns the captures and the text to parse arguments from when the message matches.
+    pub(crate) fn matches<'a>(&self, message: &'a Message) -> Option<(Option<Captures>, &'a str)> {
+        let text = message.text.as_str();
+        match self {
+            TextPattern::Regex(regex) => {
+                let captures = regex.captures(text)?;
+                let groups = captures
+                    .iter()
+                    .map(|group| group.map(|group| group.as_str().to_owned()))
+                    .collect();
+                let names = regex
+                    .capture_names()
+                    .enumerate()
+                    .filter_map(|(idx, name)| Some((name?.to_owned(), idx)))
+                    .collect();
+                Some((Some(Captures { groups, names }), text))
+            }
+            TextPattern::Prefix(prefix) => {
+                let rest = text.strip_prefix(prefix.as_str())?;
+                Some((None, rest))
+            }
+            TextPattern::IgnoreCase(expected) => {
+                (text.to_lowercase() == *expected).then_some((None, text))
+            }
+            TextPattern::Predicate(predicate) => predicate(message).then_some((None, text)),
+        }
+    }
+}
+
+/// Groups captured by a [`TextPattern::Regex`] route; group 0 is the whole match.
+#[derive(Debug, Clone)]
+pub struct Captures {
+    groups: Vec<Option<String>>,
+    names: HashMap<String, usize>,
+}
+
+impl Captures {
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.groups.get(idx)?.as_deref()
+    }
+
+    pub fn name(&self, name: &str) -> Option<&str> {
+        self.get(*self.names.get(name)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl FromUpdate for Captures {
+    fn from_update(ctx: &Context) -> Fallible<Self> {
+        ctx.captures()
+            .cloned()
+            .ok_or_else(|| format_err!("Update was not matched by a regex route"))
+    }
+}
+
+pub(crate) struct TextRoute {
+    pub(crate) pattern: TextPattern,
+    pub(crate) cb: HandlerFn<ResponseMessage>,
+}
@@ -0,0 +1,134 @@
+use crate::handler::BoxFuture;
+use crate::{Api, Bot, Fallible, Store};
+use anyhow::bail;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// When a job added with `Bot::schedule` runs.
+pub struct Schedule(Kind);
+
+enum Kind {
+    Every(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Runs the job every `period`, the first time one period after the bot starts.
+    ///
+    /// Fails when `period` is zero.
+    pub fn every(period: Duration) -> Fallible<Self> {
+        if period.is_zero() {
+            bail!("Period of a scheduled job must not be zero");
+        }
+        Ok(Schedule(Kind::Every(period)))
+    }
+
+    /// Runs the job at the times of a cron expression in UTC, including seconds:
+    /// `0 0 9 * * Mon-Fri` runs at 9:00 on weekdays.
+    pub fn cron(expression: &str) -> Fallible<Self> {
+        Ok(Schedule(Kind::Cron(Box::new(cron::Schedule::from_str(
+            expression,
+        )?))))
+    }
+
+    /// Next time the job runs, or `None` when the schedule has no more times.
+    fn next_after(&self, previous: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        match &self.0 {
+            Kind::Every(period) => {
+                let period = TimeDelta::from_std(*period).ok()?;
+                Some((previous + period).max(now))
+            }
+            Kind::Cron(schedule) => schedule.after(&previous.max(now)).next(),
+        }
+    }
+}
+
+/// What a scheduled job has access to: the bot client and its shared state.
+pub struct JobContext {
+    bot: Arc<Bot>,
+}
+
+impl JobContext {
+    pub fn api(&self) -> &Api {
+        &self.bot.api
+    }
+
+    pub fn store(&self) -> &dyn Store {
+        self.bot.store()
+    }
+
+    /// State set with `Bot::with_state`, if it is of type `S`.
+    pub fn state<S: Send + Sync + 'static>(&self) -> Option<Arc<S>> {
+        self.bot.state.clone()?.downcast().ok()
+    }
+}
+
+type JobFn = Box<dyn Fn(JobContext) -> BoxFuture<()> + Send + Sync + 'static>;
+
+pub(crate) struct Job {
+    schedule: Schedule,
+    cb: JobFn,
+}
+
+impl Job {
+    pub(crate) fn new<F, Fut>(schedule: Schedule, job: F) -> Self
+    where
+        F: Fn(JobContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Fallible<()>> + Send + 'static,
+    {
+        Self {
+            schedule,
+            cb: Box::new(move |ctx| Box::pin(job(ctx))),
+        }
+    }
+}
+
+/// Spawns one task per job of `bot`; each stops once `stopped` turns `true` or its sender is
+/// dropped, after the run in progress finishes.
+pub(crate) fn start(bot: &Arc<Bot>, stopped: &watch::Receiver<bool>) -> Vec<JoinHandle<()>> {
+    (0..bot.jobs.len())
+        .map(|idx| tokio::spawn(run(bot.clone(), idx, stopped.clone())))
+        .collect()
+}
+
+async fn run(bot: Arc<Bot>, idx: usize, mut stopped: watch::Receiver<bool>) {
+    let job = &bot.jobs[idx];
+    let mut previous = Utc::now();
+    while let Some(next) = job.schedule.next_after(previous) {
+        let delay = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stopped.wait_for(|stopped| *stopped) => return,
+        }
+
+        previous = next;
+        //TODO log
+        let _ = (job.cb)(JobContext { bot: bot.clone() }).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schedule;
+    use chrono::{TimeDelta, Utc};
+    use std::time::Duration;
+
+    #[test]
+    fn rejects_a_zero_period() {
+        assert!(Schedule::every(Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn runs_one_period_after_the_previous_run() {
+        let schedule = Schedule::every(Duration::from_secs(60)).unwrap();
+        let previous = Utc::now();
+        let next = schedule.next_after(previous).unwrap();
+        assert_eq!(next - previous, TimeDelta::seconds(60));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::Fallible;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+pub type StoreFuture<T> = Pin<Box<dyn Future<Output = Fallible<T>> + Send + 'static>>;
+
+/// Key-value state kept by the bot between updates.
+///
+/// The default [`MemoryStore`] is lost on restart; implement this trait on top of Redis or a
+/// database to share state between restarts and instances.
+pub trait Store: Send + Sync + 'static {
+    fn get(&self, key: String) -> StoreFuture<Option<Vec<u8>>>;
+
+    /// Stores `value` under `key`, replacing the previous value. Entries with a `ttl` expire
+    /// after it elapses.
+    fn set(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> StoreFuture<()>;
+
+    /// Stores `value` under `key` unless the key holds a value that has not expired, returning
+    /// whether it was stored.
+    ///
+    /// Must be atomic, e.g. `SET key value NX` in Redis: of concurrent calls for one key, only
+    /// one may return `true`.
+    fn set_if_absent(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> StoreFuture<bool>;
+
+    fn remove(&self, key: String) -> StoreFuture<()>;
+}
+
+/// Writes to a [`MemoryStore`] between two purges of its expired entries.
+const PURGE_EVERY: usize = 1024;
+
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    entries: Arc<Mutex<Entries>>,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    writes: usize,
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+impl Entries {
+    fn insert(&mut self, key: String, value: Vec<u8>, ttl: Option<Duration>) {
+        let now = Instant::now();
+        self.writes += 1;
+        if self.writes >= PURGE_EVERY {
+            self.writes = 0;
+            self.map.retain(|_, entry| !entry.is_expired(now));
+        }
+
+        let entry = Entry {
+            value,
+            expires_at: ttl.map(|ttl| now + ttl),
+        };
+        self.map.insert(key, entry);
+    }
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn get(&self, key: String) -> StoreFuture<Option<Vec<u8>>> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.map.get(&key) {
+            Some(entry) if entry.is_expired(Instant::now()) => {
+                entries.map.remove(&key);
+                None
+            }
+            entry => entry.map(|entry| entry.value.clone()),
+        };
+        Box::pin(async move { Ok(value) })
+    }
+
+    fn set(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> StoreFuture<()> {
+        self.entries.lock().unwrap().insert(key, value, ttl);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn set_if_absent(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> StoreFuture<bool> {
+        let mut entries = self.entries.lock().unwrap();
+        let is_absent = entries
+            .map
+            .get(&key)
+            .is_none_or(|entry| entry.is_expired(Instant::now()));
+        if is_absent {
+            entries.insert(key, value, ttl);
+        }
+        Box::pin(async move { Ok(is_absent) })
+    }
+
+    fn remove(&self, key: String) -> StoreFuture<()> {
+        self.entries.lock().unwrap().map.remove(&key);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryStore, Store, PURGE_EVERY};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn expires_entries_with_a_ttl() {
+        let store = MemoryStore::new();
+        store.set("kept".into(), vec![1], None).await.unwrap();
+        store
+            .set("expired".into(), vec![2], Some(Duration::ZERO))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get("kept".into()).await.unwrap(), Some(vec![1]));
+        assert_eq!(store.get("expired".into()).await.unwrap(), None);
+        assert_eq!(store.entries.lock().unwrap().map.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn purges_expired_entries_on_write() {
+        let store = MemoryStore::new();
+        for idx in 0..PURGE_EVERY {
+            store
+                .set(idx.to_string(), vec![], Some(Duration::ZERO))
+                .await
+                .unwrap();
+        }
+        assert_eq!(store.entries.lock().unwrap().map.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sets_absent_keys_only() {
+        let store = MemoryStore::new();
+        assert!(store
+            .set_if_absent("key".into(), vec![1], None)
+            .await
+            .unwrap());
+        assert!(!store
+            .set_if_absent("key".into(), vec![2], None)
+            .await
+            .unwrap());
+        assert_eq!(store.get("key".into()).await.unwrap(), Some(vec![1]));
+
+        store
+            .set("expired".into(), vec![1], Some(Duration::ZERO))
+            .await
+            .unwrap();
+        assert!(store
+            .set_if_absent("expired".into(), vec![2], None)
+            .await
+            .unwrap());
+        assert_eq!(store.get("expired".into()).await.unwrap(), Some(vec![2]));
+    }
+}
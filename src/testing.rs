@@ -0,0 +1,269 @@
+//! End-to-end tests of a [`Bot`] without network access.
+//!
+//! [`TestBot`] serves the bot on a local port and points its API calls at an in-process fake
+//! Bot API that records them, so a test can post updates and assert on what the bot sent.
+//!
+//! The module is behind the `testing` feature; enable it for tests only, as a dev-dependency.
+
+use crate::{ApiServer, Bot, ChatId, Fallible, RetryPolicy, UserId};
+use anyhow::{bail, format_err};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Id of the bot user returned by the fake `getMe`.
+pub const BOT_USER_ID: UserId = UserId(1);
+pub const BOT_USERNAME: &str = "test_bot";
+
+/// Bot API call made by the bot under test.
+#[derive(Debug, Clone)]
+pub struct ApiCall {
+    pub method: String,
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct FakeApi {
+    calls: Mutex<Vec<ApiCall>>,
+    results: Mutex<HashMap<String, Value>>,
+    errors: Mutex<HashMap<ChatId, (u16, String)>>,
+    next_message_id: AtomicU64,
+}
+
+impl FakeApi {
+    fn result(&self, method: &str, body: &Value) -> Value {
+        if let Some(result) = self.results.lock().unwrap().get(method) {
+            return result.clone();
+        }
+
+        match method {
+            "getMe" => json!({
+                "id": BOT_USER_ID,
+                "is_bot": true,
+                "first_name": "Test",
+                "username": BOT_USERNAME,
+            }),
+            "sendMessage" => json!({
+                "message_id": self.next_message_id.fetch_add(1, Ordering::Relaxed) + 1,
+                "chat": { "id": body["chat_id"], "type": chat_type(&body["chat_id"]) },
+                "text": body["text"],
+            }),
+            "getChatMember" => json!({
+                "status": "member",
+                "user": user(&body["user_id"]),
+            }),
+            "getMyCommands" => json!([]),
+            _ => json!(true),
+        }
+    }
+}
+
+async fn answer(
+    State(api): State<Arc<FakeApi>>,
+    Path((_token, method)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    let error = body["chat_id"]
+        .as_i64()
+        .and_then(|chat_id| api.errors.lock().unwrap().get(&ChatId(chat_id)).cloned());
+    let answer = match error {
+        Some((error_code, description)) => (
+            StatusCode::from_u16(error_code).unwrap_or(StatusCode::BAD_REQUEST),
+            Json(json!({ "ok": false, "error_code": error_code, "description": description })),
+        ),
+        None => (
+            StatusCode::OK,
+            Json(json!({ "ok": true, "result": api.result(&method, &body) })),
+        ),
+    };
+    api.calls.lock().unwrap().push(ApiCall { method, body });
+    answer
+}
+
+/// Bot served on a local port together with a fake Bot API, see the module docs.
+pub struct TestBot {
+    client: reqwest::Client,
+    url: String,
+    api: Arc<FakeApi>,
+    bot: Arc<Bot>,
+    next_update_id: AtomicU64,
+    tasks: [JoinHandle<()>; 2],
+}
+
+impl TestBot {
+    /// Starts `bot` against the fake API, including its startup `getMe` and `setMyCommands`
+    /// calls.
+    pub async fn start(mut bot: Bot) -> Fallible<Self> {
+        let api = Arc::new(FakeApi::default());
+        let router = Router::new()
+            .route("/{token}/{method}", post(answer))
+            .with_state(api.clone());
+        let (api_url, api_task) = serve(router).await?;
+
+        bot.set_api_server(ApiServer::new(api_url));
+        bot.set_rate_limit(None);
+        bot.set_retry_policy(RetryPolicy::none());
+        bot.prepare().await?;
+        let bot = Arc::new(bot);
+        let (url, bot_task) = serve(bot.clone().router()).await?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url,
+            api,
+            bot,
+            next_update_id: AtomicU64::new(1),
+            tasks: [api_task, bot_task],
+        })
+    }
+
+    /// Replaces the `result` the fake API returns for `method`.
+    pub fn respond(&self, method: &str, result: Value) {
+        self.api
+            .results
+            .lock()
+            .unwrap()
+            .insert(method.to_owned(), result);
+    }
+
+    /// Makes the fake API fail every call to `chat_id` with `error_code`, e.g. 403 for a
+    /// chat that blocked the bot.
+    pub fn fail_in_chat<S: Into<String>>(&self, chat_id: ChatId, error_code: u16, description: S) {
+        self.api
+            .errors
+            .lock()
+            .unwrap()
+            .insert(chat_id, (error_code, description.into()));
+    }
+
+    /// The bot under test, e.g. to send through its [`Api`](crate::Api) or read its store.
+    pub fn bot(&self) -> &Bot {
+        &self.bot
+    }
+
+    /// Posts an update to the bot and waits until it has been handled.
+    ///
+    /// `update_id` is filled in when missing.
+    pub async fn send_update(&self, mut update: Value) -> Fallible<()> {
+        let update_id = self.next_update_id.fetch_add(1, Ordering::Relaxed);
+        update
+            .as_object_mut()
+            .ok_or_else(|| format_err!("Update must be a JSON object"))?
+            .entry("update_id")
+            .or_insert(json!(update_id));
+
+        let status = self
+            .client
+            .post(&self.url)
+            .json(&update)
+            .send()
+            .await?
+            .status();
+        if !status.is_success() {
+            bail!("Bot answered the update with {}", status);
+        }
+        Ok(())
+    }
+
+    /// Sends a text message, possibly a `/command`, from `user_id` in their private chat.
+    pub async fn send_text(&self, user_id: UserId, text: &str) -> Fallible<()> {
+        self.send_text_in(user_id.into(), user_id, text).await
+    }
+
+    /// Sends a text message from `user_id` in `chat_id`; negative ids are supergroups.
+    pub async fn send_text_in(&self, chat_id: ChatId, user_id: UserId, text: &str) -> Fallible<()> {
+        let message = message(chat_id, user_id, text);
+        self.send_update(json!({ "message": message })).await
+    }
+
+    /// Presses a button with `callback_data` under a bot message in the private chat of
+    /// `user_id`.
+    ///
+    /// Take `callback_data` from [`sent_messages`](Self::sent_messages) when callbacks are
+    /// signed or stored.
+    pub async fn press_button(&self, user_id: UserId, callback_data: &str) -> Fallible<()> {
+        let id = self.next_update_id.load(Ordering::Relaxed);
+        let mut message = message(user_id.into(), BOT_USER_ID, "");
+        message["from"]["is_bot"] = json!(true);
+        let callback_query = json!({
+            "id": id.to_string(),
+            "from": user(&json!(user_id)),
+            "message": message,
+            "data": callback_data,
+        });
+        self.send_update(json!({ "callback_query": callback_query }))
+            .await
+    }
+
+    /// Every API call made so far, oldest first.
+    pub fn calls(&self) -> Vec<ApiCall> {
+        self.api.calls.lock().unwrap().clone()
+    }
+
+    /// Returns the API calls made so far and forgets them.
+    pub fn take_calls(&self) -> Vec<ApiCall> {
+        std::mem::take(&mut *self.api.calls.lock().unwrap())
+    }
+
+    /// Bodies of `sendMessage` calls made so far, oldest first.
+    pub fn sent_messages(&self) -> Vec<Value> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == "sendMessage")
+            .map(|call| call.body)
+            .collect()
+    }
+
+    /// Texts of the messages sent so far, oldest first.
+    pub fn sent_texts(&self) -> Vec<String> {
+        self.sent_messages()
+            .iter()
+            .filter_map(|body| body["text"].as_str().map(str::to_owned))
+            .collect()
+    }
+}
+
+impl Drop for TestBot {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn serve(router: Router) -> Fallible<(String, JoinHandle<()>)> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let task = tokio::spawn(async move {
+        let _ = axum::serve(listener, router).await;
+    });
+    Ok((url, task))
+}
+
+fn message(chat_id: ChatId, user_id: UserId, text: &str) -> Value {
+    json!({
+        "message_id": 1,
+        "from": user(&json!(user_id)),
+        "chat": { "id": chat_id, "type": chat_type(&json!(chat_id)) },
+        "date": 0,
+        "text": text,
+    })
+}
+
+fn user(id: &Value) -> Value {
+    json!({ "id": id, "is_bot": false, "first_name": "User" })
+}
+
+fn chat_type(chat_id: &Value) -> &'static str {
+    match chat_id.as_i64() {
+        Some(id) if id < 0 => "supergroup",
+        _ => "private",
+    }
+}
@@ -0,0 +1,133 @@
+use crate::ChatId;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Pace of outgoing messages, shared by all clones of an [`Api`](crate::Api).
+///
+/// Defaults follow the Bot API limits: 30 messages per second overall, one per second in a
+/// private chat and 20 per minute in a group.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub messages_per_second: u32,
+    pub private_chat_interval: Duration,
+    pub group_interval: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            messages_per_second: 30,
+            private_chat_interval: Duration::from_secs(1),
+            group_interval: Duration::from_secs(3),
+        }
+    }
+}
+
+/// How failed requests are retried: after the delay asked by a `429 Too Many Requests` or,
+/// for connection and server errors, after an exponential backoff.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each next one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Hands out send times so that messages respect a [`RateLimit`].
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    slots: Mutex<Slots>,
+}
+
+struct Slots {
+    next: Instant,
+    next_in_chat: HashMap<ChatId, Instant>,
+}
+
+/// Chats are forgotten once this many are tracked and their slots have passed.
+const TRACKED_CHATS: usize = 1024;
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            slots: Mutex::new(Slots {
+                next: Instant::now(),
+                next_in_chat: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Waits until a message to `chat_id` may be sent.
+    pub(crate) async fn wait(&self, chat_id: ChatId) {
+        let at = self.reserve(chat_id);
+        tokio::time::sleep_until(at).await;
+    }
+
+    fn reserve(&self, chat_id: ChatId) -> Instant {
+        let mut slots = self.slots.lock().unwrap();
+        let now = Instant::now();
+        if slots.next_in_chat.len() >= TRACKED_CHATS {
+            slots.next_in_chat.retain(|_, next| *next > now);
+        }
+
+        // The global slot advances on its own, so a backlog in one chat does not hold back
+        // messages to the others.
+        let global = slots.next.max(now);
+        slots.next = global + Duration::from_secs(1) / self.limit.messages_per_second.max(1);
+
+        let mut at = global;
+        if let Some(next) = slots.next_in_chat.get(&chat_id) {
+            at = at.max(*next);
+        }
+        let interval = match chat_id.0 < 0 {
+            true => self.limit.group_interval,
+            false => self.limit.private_chat_interval,
+        };
+        slots.next_in_chat.insert(chat_id, at + interval);
+        at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimit, RateLimiter};
+    use crate::ChatId;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn backlog_in_a_group_does_not_delay_other_chats() {
+        let limiter = RateLimiter::new(RateLimit::default());
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.reserve(ChatId(-1));
+        }
+        assert!(limiter.reserve(ChatId(-1)) - start >= Duration::from_secs(15));
+
+        assert!(limiter.reserve(ChatId(2)) - start < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,126 @@
+use boteg::testing::TestBot;
+use boteg::{AccessPolicy, ChatId, Fallible, ResponseMessage, UserId};
+use serde_json::json;
+
+mod common;
+use common::{new_bot, text, USER};
+
+const OTHER: UserId = UserId(7);
+const GROUP: ChatId = ChatId(-100);
+
+async fn secret(chat_id: ChatId) -> Fallible<ResponseMessage> {
+    text(chat_id, "secret".to_owned())
+}
+
+async fn start_with(policy: AccessPolicy) -> TestBot {
+    let mut bot = new_bot();
+    bot.add_command_static("secret", secret);
+    bot.set_access_policy(policy);
+    TestBot::start(bot).await.unwrap()
+}
+
+#[tokio::test]
+async fn allows_listed_users_only() {
+    let test_bot = start_with(AccessPolicy::new().allow_users([USER])).await;
+
+    test_bot.send_text(OTHER, "/secret").await.unwrap();
+    assert!(test_bot.sent_texts().is_empty());
+
+    test_bot.send_text(USER, "/secret").await.unwrap();
+    assert_eq!(test_bot.sent_texts(), ["secret"]);
+}
+
+#[tokio::test]
+async fn allows_anyone_in_listed_chats() {
+    let test_bot = start_with(AccessPolicy::new().allow_chats([GROUP])).await;
+
+    test_bot.send_text(OTHER, "/secret").await.unwrap();
+    assert!(test_bot.sent_texts().is_empty());
+
+    test_bot
+        .send_text_in(GROUP, OTHER, "/secret")
+        .await
+        .unwrap();
+    assert_eq!(test_bot.sent_messages()[0]["chat_id"], json!(GROUP));
+}
+
+#[tokio::test]
+async fn restricts_command_to_bot_admins() {
+    let mut bot = new_bot();
+    bot.add_command_static("secret", secret);
+    bot.set_admins([USER]);
+    bot.restrict_command("secret", AccessPolicy::new().admins_only())
+        .unwrap();
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(OTHER, "/secret").await.unwrap();
+    assert!(test_bot.sent_texts().is_empty());
+
+    test_bot.send_text(USER, "/secret").await.unwrap();
+    assert_eq!(test_bot.sent_texts(), ["secret"]);
+}
+
+#[tokio::test]
+async fn checks_chat_administrators_with_api() {
+    let test_bot = start_with(AccessPolicy::new().chat_admins_only()).await;
+
+    test_bot
+        .send_text_in(GROUP, OTHER, "/secret")
+        .await
+        .unwrap();
+    assert!(test_bot.sent_texts().is_empty());
+
+    test_bot.respond(
+        "getChatMember",
+        json!({
+            "status": "administrator",
+            "user": { "id": USER, "is_bot": false, "first_name": "User" },
+        }),
+    );
+    test_bot.send_text_in(GROUP, USER, "/secret").await.unwrap();
+    assert_eq!(test_bot.sent_texts(), ["secret"]);
+
+    let checks = test_bot
+        .calls()
+        .iter()
+        .filter(|call| call.method == "getChatMember")
+        .count();
+    assert_eq!(checks, 2);
+}
+
+#[tokio::test]
+async fn replies_when_denied() {
+    let mut bot = new_bot();
+    bot.add_command_static("secret", secret);
+    bot.set_access_policy(AccessPolicy::new().allow_users([USER]));
+    bot.reply_when_denied("Not allowed");
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(OTHER, "/secret").await.unwrap();
+    test_bot.press_button(OTHER, "anything").await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["Not allowed"]);
+    let answer = test_bot
+        .calls()
+        .into_iter()
+        .find(|call| call.method == "answerCallbackQuery")
+        .unwrap();
+    assert_eq!(answer.body["text"], "Not allowed");
+}
+
+#[tokio::test]
+async fn restricts_inline_command_of_the_same_name() {
+    let mut bot = new_bot();
+    bot.add_command_static("secret", secret);
+    bot.add_command_inline_static("secret", secret);
+    bot.restrict_command("secret", AccessPolicy::new().allow_users([USER]))
+        .unwrap();
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(OTHER, "/secret").await.unwrap();
+    test_bot.send_text(OTHER, "secret").await.unwrap();
+    assert!(test_bot.sent_texts().is_empty());
+
+    test_bot.send_text(USER, "secret").await.unwrap();
+    assert_eq!(test_bot.sent_texts(), ["secret"]);
+}
@@ -0,0 +1,104 @@
+use boteg::testing::{TestBot, BOT_USER_ID};
+use boteg::{AccessPolicy, Broadcast, ChatId, Outcome, UserId};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{new_bot, text, USER};
+
+const FIRST: ChatId = ChatId(10);
+const SECOND: ChatId = ChatId(20);
+const BLOCKING: ChatId = ChatId(30);
+
+async fn start() -> TestBot {
+    let test_bot = TestBot::start(new_bot()).await.unwrap();
+    test_bot.fail_in_chat(BLOCKING, 403, "Forbidden: bot was blocked by the user");
+    test_bot
+}
+
+/// Sends broadcast `id` to `chats` and returns the outcome in every chat.
+async fn broadcast(test_bot: &TestBot, id: &str, chats: &[ChatId]) -> Vec<(ChatId, Outcome)> {
+    let outcomes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = outcomes.clone();
+    let broadcast = Broadcast::new(id, text(ChatId(0), "news".to_owned()).unwrap()).on_outcome(
+        move |chat_id, outcome, _| recorded.lock().unwrap().push((chat_id, outcome.clone())),
+    );
+    let bot = test_bot.bot();
+    broadcast
+        .send_all(bot.api(), bot.store(), chats.to_vec())
+        .await
+        .unwrap();
+    let outcomes = outcomes.lock().unwrap().clone();
+    outcomes
+}
+
+fn sends_to(test_bot: &TestBot, chat_id: ChatId) -> usize {
+    test_bot
+        .sent_messages()
+        .iter()
+        .filter(|body| body["chat_id"] == json!(chat_id))
+        .count()
+}
+
+#[tokio::test]
+async fn skips_chats_that_blocked_the_bot() {
+    let test_bot = start().await;
+
+    let outcomes = broadcast(&test_bot, "first", &[FIRST, BLOCKING]).await;
+    assert_eq!(
+        outcomes,
+        [(FIRST, Outcome::Sent), (BLOCKING, Outcome::Blocked)]
+    );
+
+    let outcomes = broadcast(&test_bot, "second", &[BLOCKING]).await;
+    assert_eq!(outcomes, [(BLOCKING, Outcome::Skipped)]);
+    assert_eq!(sends_to(&test_bot, BLOCKING), 1);
+}
+
+#[tokio::test]
+async fn resumes_without_sending_twice() {
+    let test_bot = start().await;
+
+    broadcast(&test_bot, "news", &[FIRST]).await;
+    let outcomes = broadcast(&test_bot, "news", &[FIRST, SECOND]).await;
+
+    assert_eq!(
+        outcomes,
+        [(FIRST, Outcome::Skipped), (SECOND, Outcome::Sent)]
+    );
+    assert_eq!(sends_to(&test_bot, FIRST), 1);
+}
+
+#[tokio::test]
+async fn unblocks_chat_added_back_by_denied_user() {
+    let mut bot = new_bot();
+    bot.set_access_policy(AccessPolicy::new().allow_users([USER]));
+    let test_bot = TestBot::start(bot).await.unwrap();
+    test_bot.fail_in_chat(BLOCKING, 403, "Forbidden: bot was blocked by the user");
+    broadcast(&test_bot, "first", &[BLOCKING]).await;
+
+    let member = |status: &str| {
+        json!({
+            "status": status,
+            "user": { "id": BOT_USER_ID, "is_bot": true, "first_name": "Test" },
+            "until_date": 0,
+        })
+    };
+    let other = UserId(BLOCKING.0);
+    test_bot
+        .send_update(json!({
+            "my_chat_member": {
+                "chat": { "id": BLOCKING, "type": "private" },
+                "from": { "id": other, "is_bot": false, "first_name": "User" },
+                "date": 0,
+                "old_chat_member": member("kicked"),
+                "new_chat_member": member("member"),
+            }
+        }))
+        .await
+        .unwrap();
+
+    // Tried again instead of skipped, so the chat is no longer known as blocked.
+    let outcomes = broadcast(&test_bot, "second", &[BLOCKING]).await;
+    assert_eq!(outcomes, [(BLOCKING, Outcome::Blocked)]);
+}
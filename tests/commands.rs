@@ -0,0 +1,126 @@
+use boteg::testing::TestBot;
+use boteg::{Args, ChatId, Fallible, FromArgs, ResponseMessage};
+
+mod common;
+use common::{new_bot, text, USER};
+
+#[derive(FromArgs)]
+struct Transfer {
+    amount: u64,
+    currency: String,
+    to: Option<String>,
+}
+
+#[derive(FromArgs)]
+struct Page {
+    #[args(default)]
+    number: u32,
+}
+
+async fn transfer(chat_id: ChatId, transfer: Transfer) -> Fallible<ResponseMessage> {
+    let to = transfer.to.as_deref().unwrap_or("me");
+    text(
+        chat_id,
+        format!("{} {} to {}", transfer.amount, transfer.currency, to),
+    )
+}
+
+async fn page(chat_id: ChatId, page: Page) -> Fallible<ResponseMessage> {
+    text(chat_id, format!("page {}", page.number))
+}
+
+async fn echo(chat_id: ChatId, args: Args) -> Fallible<ResponseMessage> {
+    text(chat_id, format!("echo: {}", args.raw()))
+}
+
+#[tokio::test]
+async fn runs_a_command_without_arguments() {
+    let mut bot = new_bot();
+    bot.add_command_static("page", page);
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(USER, "/page").await.unwrap();
+    test_bot.send_text(USER, "/page 3").await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["page 0", "page 3"]);
+}
+
+#[tokio::test]
+async fn shows_the_usage_of_a_bare_command() {
+    let mut bot = new_bot();
+    bot.add_command_static("transfer", transfer);
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(USER, "/transfer").await.unwrap();
+    test_bot.send_text(USER, "/transfer 5 EUR").await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        [
+            "Missing argument #1\nUsage: /transfer <amount> <currency> [to]",
+            "5 EUR to me",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn selects_a_bare_command_when_current_command_is_enabled() {
+    let mut bot = new_bot();
+    bot.add_command_static("echo", echo);
+    bot.enable_current_command();
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(USER, "/echo").await.unwrap();
+    test_bot.send_text(USER, "hello").await.unwrap();
+    test_bot.send_text(USER, "/current").await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        ["Command set to echo", "echo: hello", "echo"]
+    );
+}
+
+async fn fallback(chat_id: ChatId, args: Args) -> Fallible<ResponseMessage> {
+    text(chat_id, format!("fallback: {}", args.raw()))
+}
+
+#[tokio::test]
+async fn sends_text_to_the_default_handler() {
+    let mut bot = new_bot();
+    bot.add_command_static("echo", echo);
+    bot.set_default_handler(fallback);
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(USER, "hello").await.unwrap();
+    test_bot.send_text(USER, "/echo").await.unwrap();
+    test_bot.send_text(USER, "again").await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        ["fallback: hello", "echo: ", "fallback: again"]
+    );
+}
+
+#[tokio::test]
+async fn sends_text_to_the_default_handler_until_a_command_is_selected() {
+    let mut bot = new_bot();
+    bot.add_command_static("echo", echo);
+    bot.set_default_handler(fallback);
+    bot.enable_current_command();
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    test_bot.send_text(USER, "/current").await.unwrap();
+    test_bot.send_text(USER, "hello").await.unwrap();
+    test_bot.send_text(USER, "/echo").await.unwrap();
+    test_bot.send_text(USER, "again").await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        [
+            "No command is set",
+            "fallback: hello",
+            "Command set to echo",
+            "echo: again"
+        ]
+    );
+}
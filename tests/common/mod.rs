@@ -0,0 +1,24 @@
+#![allow(dead_code)]
+
+use boteg::{Bot, ChatId, Fallible, ResponseMessage, UserId};
+
+pub const USER: UserId = UserId(42);
+
+/// Bot without handlers, to be started with `TestBot::start`.
+pub fn new_bot() -> Bot {
+    #[cfg(not(feature = "tls"))]
+    let bot = Bot::new(([127, 0, 0, 1], 0), "token".to_owned());
+    #[cfg(feature = "tls")]
+    let bot = Bot::new(([127, 0, 0, 1], 0), "token".to_owned(), None, None);
+
+    bot.unwrap()
+}
+
+pub fn text(chat_id: ChatId, text: String) -> Fallible<ResponseMessage> {
+    Ok(ResponseMessage {
+        chat_id,
+        text,
+        parse_mode: None,
+        reply_markup: None,
+    })
+}
@@ -0,0 +1,339 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use boteg::testing::TestBot;
+use boteg::{
+    Api, Args, Bot, CallbackData, ChatId, Fallible, InlineKeyboardButton, InlineKeyboardMarkup,
+    MemoryStore, Message, Payload, ResponseMessage, Store, StoreFuture, User,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+use common::{new_bot, text, USER};
+
+async fn echo(chat_id: ChatId, args: Args) -> Fallible<ResponseMessage> {
+    text(chat_id, format!("echo: {}", args.raw()))
+}
+
+async fn menu(chat_id: ChatId) -> Fallible<ResponseMessage> {
+    let button = |label: &str, amount: u32| -> Fallible<InlineKeyboardButton> {
+        Ok(InlineKeyboardButton {
+            text: label.to_owned(),
+            callback_data: CallbackData::with_payload("buy", &amount)?,
+        })
+    };
+    Ok(ResponseMessage {
+        reply_markup: Some(InlineKeyboardMarkup {
+            inline_keyboard: [vec![button("One", 1)?, button("Ten", 10)?]],
+        }),
+        ..text(chat_id, "How many?".to_owned())?
+    })
+}
+
+async fn ping(chat_id: ChatId) -> Fallible<ResponseMessage> {
+    text(chat_id, "pong".to_owned())
+}
+
+async fn buy(chat_id: ChatId, Payload(amount): Payload<u32>) -> Fallible<ResponseMessage> {
+    text(chat_id, format!("bought {}", amount))
+}
+
+/// Sends the keyboard through the client, as a background job would.
+async fn notify(api: Api, chat_id: ChatId) -> Fallible<ResponseMessage> {
+    let big = "x".repeat(CallbackData::MAX_LEN);
+    let button = InlineKeyboardButton {
+        text: "Echo".to_owned(),
+        callback_data: CallbackData::with_large_payload("shout", &big)?,
+    };
+    api.send_message(ResponseMessage {
+        reply_markup: Some(InlineKeyboardMarkup {
+            inline_keyboard: [vec![button]],
+        }),
+        ..menu(chat_id).await?
+    })
+    .await?;
+    text(chat_id, "sent".to_owned())
+}
+
+async fn shout(chat_id: ChatId, Payload(text): Payload<String>) -> Fallible<ResponseMessage> {
+    self::text(chat_id, text.to_uppercase())
+}
+
+fn bot() -> Bot {
+    let mut bot = new_bot();
+    bot.add_command_static("echo", echo);
+    bot.add_command_static("menu", menu);
+    bot.add_callback_static("ping", |chat_id: ChatId| ping(chat_id));
+    bot.add_callback_static("buy", buy);
+    bot.add_command_static("notify", notify);
+    bot.add_callback_static("shout", shout);
+    bot
+}
+
+/// `callback_data` of the buttons in the last keyboard the bot sent.
+fn buttons(test_bot: &TestBot) -> Vec<String> {
+    let messages = test_bot.sent_messages();
+    let keyboard = messages
+        .iter()
+        .rev()
+        .map(|message| &message["reply_markup"]["inline_keyboard"][0])
+        .find(|keyboard| keyboard.is_array())
+        .unwrap();
+    keyboard
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|button| button["callback_data"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+/// Update with `/echo once` and a fixed id, as Telegram delivers it again after a failure.
+fn echo_once(update_id: u64) -> Value {
+    json!({
+        "update_id": update_id,
+        "message": {
+            "message_id": 1,
+            "from": { "id": USER, "is_bot": false, "first_name": "User" },
+            "chat": { "id": USER, "type": "private" },
+            "date": 0,
+            "text": "/echo once",
+        },
+    })
+}
+
+fn answered_callbacks(test_bot: &TestBot) -> usize {
+    test_bot
+        .calls()
+        .iter()
+        .filter(|call| call.method == "answerCallbackQuery")
+        .count()
+}
+
+#[tokio::test]
+async fn replies_to_a_command() {
+    let test_bot = TestBot::start(bot()).await.unwrap();
+    test_bot.send_text(USER, "/echo hello world").await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["echo: hello world"]);
+    assert_eq!(test_bot.sent_messages()[0]["chat_id"], json!(USER));
+}
+
+#[tokio::test]
+async fn handles_a_button_press() {
+    let test_bot = TestBot::start(bot()).await.unwrap();
+    test_bot.press_button(USER, "ping").await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["pong"]);
+}
+
+#[tokio::test]
+async fn passes_the_payload_to_the_callback() {
+    let test_bot = TestBot::start(bot()).await.unwrap();
+    test_bot.send_text(USER, "/menu now").await.unwrap();
+    let buttons = buttons(&test_bot);
+
+    test_bot.press_button(USER, &buttons[1]).await.unwrap();
+    test_bot.press_button(USER, &buttons[0]).await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        ["How many?", "bought 10", "bought 1"]
+    );
+}
+
+#[tokio::test]
+async fn handles_a_redelivered_update_once() {
+    let test_bot = TestBot::start(bot()).await.unwrap();
+    let update = echo_once(7);
+    test_bot.send_update(update.clone()).await.unwrap();
+    test_bot.send_update(update).await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["echo: once"]);
+}
+
+/// Store whose next write fails while `failing` is set.
+#[derive(Clone, Default)]
+struct FlakyStore {
+    store: MemoryStore,
+    failing: Arc<AtomicBool>,
+}
+
+impl FlakyStore {
+    fn fail(&self) -> Option<StoreFuture<bool>> {
+        match self.failing.swap(false, Ordering::Relaxed) {
+            true => Some(Box::pin(async {
+                Err(anyhow::format_err!("Store is down"))
+            })),
+            false => None,
+        }
+    }
+}
+
+impl Store for FlakyStore {
+    fn get(&self, key: String) -> StoreFuture<Option<Vec<u8>>> {
+        self.store.get(key)
+    }
+
+    fn set(&self, key: String, value: Vec<u8>, ttl: Option<Duration>) -> StoreFuture<()> {
+        self.store.set(key, value, ttl)
+    }
+
+    fn set_if_absent(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> StoreFuture<bool> {
+        self.fail()
+            .unwrap_or_else(|| self.store.set_if_absent(key, value, ttl))
+    }
+
+    fn remove(&self, key: String) -> StoreFuture<()> {
+        self.store.remove(key)
+    }
+}
+
+#[tokio::test]
+async fn handles_an_update_redelivered_after_a_store_error() {
+    let store = FlakyStore::default();
+    let mut bot = bot();
+    bot.set_store(store.clone());
+    bot.persist_update_ids(Duration::from_secs(60));
+    let test_bot = TestBot::start(bot).await.unwrap();
+    let update = echo_once(7);
+
+    store.failing.store(true, Ordering::Relaxed);
+    assert!(test_bot.send_update(update.clone()).await.is_err());
+    test_bot.send_update(update.clone()).await.unwrap();
+    test_bot.send_update(update).await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["echo: once"]);
+}
+
+#[tokio::test]
+async fn runs_a_handler_once_when_its_reply_is_not_delivered() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let mut bot = new_bot();
+    bot.add_command_static("echo", move |chat_id: ChatId| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        async move { text(chat_id, "echo".to_owned()) }
+    });
+    let test_bot = TestBot::start(bot).await.unwrap();
+    test_bot.fail_in_chat(USER.into(), 400, "Bad Request: chat not found");
+    let update = echo_once(7);
+
+    test_bot.send_update(update.clone()).await.unwrap();
+    test_bot.send_update(update).await.unwrap();
+
+    assert_eq!(runs.load(Ordering::Relaxed), 1);
+    assert_eq!(test_bot.sent_messages().len(), 1);
+}
+
+#[tokio::test]
+async fn runs_signed_callbacks() {
+    let mut bot = bot();
+    bot.sign_callbacks("secret");
+    let test_bot = TestBot::start(bot).await.unwrap();
+    test_bot.send_text(USER, "/menu now").await.unwrap();
+    let buttons = buttons(&test_bot);
+
+    test_bot.press_button(USER, &buttons[0]).await.unwrap();
+
+    assert_eq!(test_bot.sent_texts(), ["How many?", "bought 1"]);
+}
+
+#[tokio::test]
+async fn drops_forged_callbacks() {
+    let mut bot = bot();
+    bot.sign_callbacks("secret");
+    let test_bot = TestBot::start(bot).await.unwrap();
+    test_bot.send_text(USER, "/menu now").await.unwrap();
+    let signed = buttons(&test_bot).remove(1);
+    let (data, _signature) = signed.rsplit_once('.').unwrap();
+    test_bot.take_calls();
+
+    test_bot.press_button(USER, "ping").await.unwrap();
+    test_bot
+        .press_button(USER, &format!("{}.AAAAAAAAAAA", data))
+        .await
+        .unwrap();
+
+    assert!(test_bot.sent_texts().is_empty());
+    assert_eq!(answered_callbacks(&test_bot), 2);
+}
+
+#[tokio::test]
+async fn drops_callbacks_with_a_truncated_signature() {
+    let mut bot = bot();
+    bot.sign_callbacks("secret");
+    let test_bot = TestBot::start(bot).await.unwrap();
+    test_bot.send_text(USER, "/menu now").await.unwrap();
+    let signed = buttons(&test_bot).remove(1);
+    let (data, _signature) = signed.rsplit_once('.').unwrap();
+    test_bot.take_calls();
+
+    // Every 1-byte tag, as an attacker guessing a short signature would try.
+    for byte in 0..=u8::MAX {
+        let tag = URL_SAFE_NO_PAD.encode([byte]);
+        test_bot
+            .press_button(USER, &format!("{}.{}", data, tag))
+            .await
+            .unwrap();
+    }
+
+    assert!(test_bot.sent_texts().is_empty());
+    assert_eq!(answered_callbacks(&test_bot), 256);
+}
+
+#[tokio::test]
+async fn prepares_keyboards_sent_through_the_client() {
+    let mut bot = bot();
+    bot.sign_callbacks("secret");
+    bot.store_large_callbacks(Duration::from_secs(60));
+    let test_bot = TestBot::start(bot).await.unwrap();
+    test_bot.send_text(USER, "/notify now").await.unwrap();
+    let buttons = buttons(&test_bot);
+    assert!(buttons[0].len() <= CallbackData::MAX_LEN);
+
+    test_bot.press_button(USER, &buttons[0]).await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        ["How many?", "sent", &"X".repeat(CallbackData::MAX_LEN)]
+    );
+}
+
+async fn who(user: User, message: Option<Message>) -> Fallible<ResponseMessage> {
+    let place = match message {
+        Some(_) => "chat",
+        None => "inline message",
+    };
+    text(user.id.into(), format!("pressed in {}", place))
+}
+
+#[tokio::test]
+async fn handles_a_button_of_an_inline_message() {
+    let mut bot = bot();
+    bot.add_callback_static("who", who);
+    let test_bot = TestBot::start(bot).await.unwrap();
+    let callback_query = json!({
+        "id": "1",
+        "from": { "id": USER, "is_bot": false, "first_name": "User" },
+        "inline_message_id": "AAQ",
+        "chat_instance": "1",
+        "data": "who",
+    });
+
+    test_bot
+        .send_update(json!({ "callback_query": callback_query }))
+        .await
+        .unwrap();
+    test_bot.press_button(USER, "who").await.unwrap();
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        ["pressed in inline message", "pressed in chat"]
+    );
+}
@@ -0,0 +1,128 @@
+use boteg::{Args, FromArgs};
+
+#[derive(FromArgs, Debug, PartialEq)]
+struct Transfer {
+    amount: u64,
+    currency: String,
+    to: Option<String>,
+}
+
+#[derive(FromArgs, Debug, PartialEq)]
+struct Page {
+    #[args(default)]
+    number: u32,
+    #[args(default = "20")]
+    size: u32,
+    #[args(default = 1 + 1)]
+    columns: u32,
+}
+
+#[derive(FromArgs, Debug, PartialEq)]
+struct Note {
+    title: String,
+    #[args(rest)]
+    text: String,
+}
+
+#[derive(FromArgs, Debug, PartialEq)]
+struct Tags {
+    #[args(rest)]
+    tags: Vec<u8>,
+}
+
+#[derive(FromArgs, Debug, PartialEq)]
+struct BadDefault {
+    #[args(default = "many")]
+    count: u32,
+}
+
+fn parse<T: FromArgs>(raw: &str) -> Result<T, String> {
+    T::from_args(&Args::parse(raw)).map_err(|err| err.to_string())
+}
+
+#[test]
+fn required_arguments() {
+    assert_eq!(
+        parse::<Transfer>("100 EUR"),
+        Ok(Transfer {
+            amount: 100,
+            currency: "EUR".to_owned(),
+            to: None,
+        })
+    );
+    assert_eq!(
+        parse::<Transfer>("100"),
+        Err::<Transfer, _>("Missing argument #2".to_owned())
+    );
+    assert!(parse::<Transfer>("lots EUR")
+        .unwrap_err()
+        .starts_with("Invalid argument `lots`"));
+}
+
+#[test]
+fn optional_arguments() {
+    assert_eq!(
+        parse::<Transfer>("100 EUR alice").map(|transfer| transfer.to),
+        Ok(Some("alice".to_owned()))
+    );
+    assert_eq!(Transfer::usage(), "<amount> <currency> [to]");
+}
+
+#[test]
+fn default_arguments() {
+    assert_eq!(
+        parse::<Page>(""),
+        Ok(Page {
+            number: 0,
+            size: 20,
+            columns: 2,
+        })
+    );
+    assert_eq!(
+        parse::<Page>("3 50 4"),
+        Ok(Page {
+            number: 3,
+            size: 50,
+            columns: 4,
+        })
+    );
+    assert_eq!(Page::usage(), "[number] [size] [columns]");
+}
+
+#[test]
+fn invalid_default_is_an_error() {
+    assert_eq!(
+        parse::<BadDefault>(""),
+        Err::<BadDefault, _>(
+            "Invalid default for `count`: invalid digit found in string".to_owned()
+        )
+    );
+    assert_eq!(parse::<BadDefault>("7"), Ok(BadDefault { count: 7 }));
+}
+
+#[test]
+fn rest_arguments() {
+    assert_eq!(
+        parse::<Note>(r#"todo buy "oat milk""#),
+        Ok(Note {
+            title: "todo".to_owned(),
+            text: "buy oat milk".to_owned(),
+        })
+    );
+    assert_eq!(
+        parse::<Tags>("1 2 3"),
+        Ok(Tags {
+            tags: vec![1, 2, 3]
+        })
+    );
+    assert_eq!(parse::<Tags>(""), Ok(Tags { tags: vec![] }));
+    assert_eq!(Note::usage(), "<title> [text...]");
+}
+
+#[test]
+fn too_many_arguments() {
+    assert_eq!(
+        parse::<Transfer>("100 EUR alice bob"),
+        Err::<Transfer, _>("Too many arguments, expected at most 3".to_owned())
+    );
+}
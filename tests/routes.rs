@@ -0,0 +1,115 @@
+use boteg::testing::TestBot;
+use boteg::{Args, Captures, ChatId, Fallible, ResponseMessage, TextPattern};
+
+mod common;
+use common::{new_bot, text, USER};
+
+async fn convert(chat_id: ChatId, captures: Captures) -> Fallible<ResponseMessage> {
+    let amount = captures.name("amount").unwrap_or_default();
+    let currency = captures.name("currency").unwrap_or_default();
+    text(chat_id, format!("convert {} of {}", amount, currency))
+}
+
+async fn buy(chat_id: ChatId, args: Args) -> Fallible<ResponseMessage> {
+    let items: Vec<_> = args.iter().collect();
+    text(chat_id, format!("buy {}", items.join("+")))
+}
+
+fn reply(answer: &'static str) -> impl Fn(ChatId) -> std::future::Ready<Fallible<ResponseMessage>> {
+    move |chat_id| std::future::ready(text(chat_id, answer.to_owned()))
+}
+
+async fn send_all(test_bot: &TestBot, texts: &[&str]) {
+    for text in texts {
+        test_bot.send_text(USER, text).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn matches_each_kind_of_pattern() {
+    let mut bot = new_bot();
+    bot.add_text_route(
+        TextPattern::regex(r"^(?P<amount>\d+) (?P<currency>[A-Z]{3})$").unwrap(),
+        convert,
+    );
+    bot.add_text_route(TextPattern::prefix("buy "), buy);
+    bot.add_text_route(TextPattern::ignore_case("Hello"), reply("hi"));
+    bot.add_text_route(
+        TextPattern::predicate(|message| message.text.ends_with('?')),
+        reply("good question"),
+    );
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    send_all(
+        &test_bot,
+        &[
+            "10 EUR",
+            "buy milk \"rye bread\"",
+            "hELLo",
+            "why?",
+            "10 eur",
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        test_bot.sent_texts(),
+        [
+            "convert 10 of EUR",
+            "buy milk+rye bread",
+            "hi",
+            "good question"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tries_routes_in_registration_order() {
+    let mut bot = new_bot();
+    bot.add_text_route(TextPattern::prefix("status"), reply("status"));
+    bot.add_text_route(TextPattern::regex("^stat").unwrap(), reply("stat"));
+    bot.add_text_route(TextPattern::regex("").unwrap(), reply("anything"));
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    send_all(&test_bot, &["status", "stats", "other"]).await;
+
+    assert_eq!(test_bot.sent_texts(), ["status", "stat", "anything"]);
+}
+
+#[tokio::test]
+async fn prefers_an_exact_inline_command() {
+    let mut bot = new_bot();
+    bot.add_command_inline_static("help", reply("inline help"));
+    bot.add_text_route(TextPattern::prefix("help"), reply("help route"));
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    send_all(&test_bot, &["help", "help me"]).await;
+
+    assert_eq!(test_bot.sent_texts(), ["inline help", "help route"]);
+}
+
+#[tokio::test]
+async fn exposes_numbered_and_named_captures() {
+    let mut bot = new_bot();
+    bot.add_text_route(
+        TextPattern::regex(r"^(\w+)@(?P<host>\w+)(\.com)?$").unwrap(),
+        |chat_id: ChatId, captures: Captures| async move {
+            text(
+                chat_id,
+                format!(
+                    "{} {} {} {:?} {:?}",
+                    captures.len(),
+                    captures.get(0).unwrap(),
+                    captures.get(1).unwrap(),
+                    captures.name("host"),
+                    captures.get(3),
+                ),
+            )
+        },
+    );
+    let test_bot = TestBot::start(bot).await.unwrap();
+
+    send_all(&test_bot, &["me@home"]).await;
+
+    assert_eq!(test_bot.sent_texts(), [r#"4 me@home me Some("home") None"#]);
+}